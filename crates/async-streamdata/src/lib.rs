@@ -14,8 +14,7 @@
 
 pub mod reader;
 
-use async_stream::try_stream;
-use bytes::Buf;
+use async_stream::stream;
 use futures_core::Stream;
 
 /// A type alias for a more compact [`Error`] declaration.
@@ -44,30 +43,89 @@ pub trait Reader {
 }
 
 /// Converts the given reader to a stream of decoded values.
+///
+/// See [`stream_with_offsets`].
 pub fn stream<Reader, Decoder, Buffer>(
+    reader: Reader,
+    state: streamdata::State<Decoder, Buffer>,
+) -> impl Stream<Item = ResultFor<Reader, Decoder, Buffer>>
+where
+    Reader: self::Reader,
+    Decoder: streamdata::Decoder<Buffer>,
+    Buffer: streamdata::Buffer,
+{
+    let decoded = stream_with_offsets(reader, state);
+    stream! {
+        for await result in decoded {
+            yield result.map(|decoded| decoded.value);
+        }
+    }
+}
+
+/// Converts the given reader to a stream of decoded values accompanied by
+/// their positions in the stream.
+///
+/// The stream ends after the first error, unless it is a decoding error and
+/// the [`streamdata::State::recovery`] policy allows carrying on, in which
+/// case the decoding resumes after the error is yielded (like with
+/// [`streamdata::io::Iter`]).
+///
+/// See [`streamdata::AvailableIter::with_offsets`].
+pub fn stream_with_offsets<Reader, Decoder, Buffer>(
     mut reader: Reader,
     mut state: streamdata::State<Decoder, Buffer>,
-) -> impl Stream<Item = ResultFor<Reader, Decoder, Buffer>>
+) -> impl Stream<
+    Item = Result<
+        streamdata::Decoded<<Decoder as streamdata::Decoder<Buffer>>::Value>,
+        ErrorFor<Reader, Decoder, Buffer>,
+    >,
+>
 where
     Reader: self::Reader,
     Decoder: streamdata::Decoder<Buffer>,
     Buffer: streamdata::Buffer,
 {
-    try_stream! {
+    stream! {
+        let short_circuit = matches!(state.recovery, streamdata::Recovery::ShortCircuit);
         while let Some(data) = reader.next().await {
-            let data = data.map_err(Error::Reading)?;
-            let results = state.process_next_chunk(data.chunk());
+            let data = match data {
+                Ok(data) => data,
+                Err(err) => {
+                    yield Err(Error::Reading(err));
+                    return;
+                }
+            };
+            let results = match state.try_process_next_buf(data) {
+                Ok(results) => results.with_offsets(),
+                Err(err) => {
+                    yield Err(Error::BufferLimitExceeded(err));
+                    return;
+                }
+            };
             for result in results {
-                let value = result.map_err(Error::Decoding)?;
-                yield value;
+                let failed = result.is_err();
+                yield result.map_err(Error::Decoding);
+                if failed && short_circuit {
+                    return;
+                }
+            }
+        }
+        for result in state.process_eof().with_offsets() {
+            let failed = result.is_err();
+            yield result.map_err(Error::Decoding);
+            if failed && short_circuit {
+                return;
             }
         }
-        state.finish().map_err(|data| Error::UndecodedDataLeftUponCompletion { data })?;
+        if let Err(data) = state.finish() {
+            yield Err(Error::UndecodedDataLeftUponCompletion { data });
+        }
     }
 }
 
 /// Errors that can occur while decoding the data stream.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error<ReaderError, DecoderError, Buffer> {
     /// An error has occured while reading the data.
     #[error("reading: {0}")]
@@ -75,6 +133,10 @@ pub enum Error<ReaderError, DecoderError, Buffer> {
     /// An error has occured while decoding the values.
     #[error("decoding: {0}")]
     Decoding(#[source] DecoderError),
+    /// The data could not be buffered because of the
+    /// [`streamdata::State::buffer_limit`].
+    #[error("buffering: {0}")]
+    BufferLimitExceeded(#[source] streamdata::BufferLimitExceeded),
     /// After all the data was read and the decoding was complete there is still
     /// some data in the state buffer.
     #[error("some data left in the buffer after the data was read completely")]
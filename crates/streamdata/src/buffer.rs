@@ -1,5 +1,12 @@
 //! Buffer implementations.
 
+pub mod compacting;
+pub mod pool;
+#[cfg(feature = "bytes")]
+pub mod rope;
+#[cfg(feature = "spill")]
+pub mod spill;
+
 /// [`Buffer`] captures the interface we require from the piece that maintains
 /// the [`crate::State`] buffer.
 /// This buffer is intended for keeping the undecoded partial chunks.
@@ -10,6 +17,36 @@ pub trait Buffer {
     fn view(&self) -> &[u8];
     /// Drop the given amout of bytes from the start of the buffer.
     fn advance(&mut self, bytes: usize);
+
+    /// The amount of bytes currently in the buffer.
+    fn len(&self) -> usize {
+        self.view().len()
+    }
+
+    /// Returns `true` if the buffer contains no bytes.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append all the remaining data from the given [`bytes::Buf`] to the end
+    /// of the buffer.
+    ///
+    /// The default implementation copies the data chunk by chunk via
+    /// [`Self::append`]; buffers that can hold on to [`bytes::Bytes`] directly
+    /// override this to avoid copying.
+    #[cfg(feature = "bytes")]
+    fn append_buf<B>(&mut self, mut buf: B)
+    where
+        B: bytes::Buf,
+        Self: Sized,
+    {
+        while buf.has_remaining() {
+            let chunk = buf.chunk();
+            let chunk_len = chunk.len();
+            self.append(chunk);
+            buf.advance(chunk_len);
+        }
+    }
 }
 
 impl super::Buffer for Vec<u8> {
@@ -0,0 +1,274 @@
+//! Offset-compacting buffer.
+
+/// The default value for the [`Buffer::compaction_threshold`].
+pub const DEFAULT_COMPACTION_THRESHOLD: usize = 4 * 1024;
+
+/// A [`crate::Buffer`] that advances in amortized O(1).
+///
+/// Instead of shifting the data on every advance (like [`Vec<u8>`] does), it
+/// moves the offset of the first live byte, and only compacts the storage
+/// (moving the live data to the front) when the consumed prefix gets large
+/// enough: at least the compaction threshold and at least the size of
+/// the live data.
+/// The storage is also compacted instead of being grown when the append would
+/// reallocate and the consumed prefix is large enough by the same measure
+/// (either reaching the threshold or the size of the live data), so that
+/// the copy is amortized against the consumed bytes.
+#[derive(Debug, Clone)]
+pub struct Buffer {
+    /// The underlying storage; the live data is `data[start..]`.
+    data: Vec<u8>,
+    /// The offset of the first live byte in the `data`.
+    start: usize,
+    /// The minimal amount of consumed bytes to accumulate before compacting.
+    compaction_threshold: usize,
+}
+
+impl Buffer {
+    /// Create a new empty [`Buffer`] with the default compaction threshold.
+    pub const fn new() -> Self {
+        Self::with_compaction_threshold(DEFAULT_COMPACTION_THRESHOLD)
+    }
+
+    /// Create a new empty [`Buffer`] with the given compaction threshold.
+    pub const fn with_compaction_threshold(compaction_threshold: usize) -> Self {
+        Self {
+            data: Vec::new(),
+            start: 0,
+            compaction_threshold,
+        }
+    }
+
+    /// The minimal amount of consumed bytes to accumulate before
+    /// the storage is compacted.
+    pub const fn compaction_threshold(&self) -> usize {
+        self.compaction_threshold
+    }
+
+    /// The capacity of the underlying storage.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Shrink the capacity of the underlying storage to the given amount of
+    /// bytes, or to the amount of the data in the buffer, whichever is
+    /// greater.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        if self.start > 0 {
+            self.compact();
+        }
+        self.data.shrink_to(min_capacity);
+    }
+
+    /// The capacity the storage needs to fit the given amount of bytes
+    /// appended to it without growing, taking into account the compaction
+    /// the append would do.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn required_capacity(&self, additional: usize) -> usize {
+        let capacity = self.data.capacity();
+        if self.data.len() + additional <= capacity {
+            return capacity;
+        }
+        if self.should_compact() {
+            capacity.max(crate::Buffer::len(self) + additional)
+        } else {
+            self.data.len() + additional
+        }
+    }
+
+    /// Grow the storage to at least the given capacity, compacting it first
+    /// if the consumed prefix is large enough.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn grow_to(&mut self, capacity: usize) {
+        if capacity <= self.data.capacity() {
+            return;
+        }
+        if self.should_compact() {
+            self.compact();
+        }
+        self.data.reserve_exact(capacity - self.data.len());
+    }
+
+    /// Drop all the data from the buffer, keeping the allocated storage.
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.start = 0;
+    }
+
+    /// Whether the consumed prefix is large enough to be worth compacting
+    /// instead of growing the storage.
+    fn should_compact(&self) -> bool {
+        self.start > 0
+            && (self.start >= self.compaction_threshold || self.start >= crate::Buffer::len(self))
+    }
+
+    /// Move the live data to the start of the storage.
+    fn compact(&mut self) {
+        self.data.copy_within(self.start.., 0);
+        self.data.truncate(crate::Buffer::len(self));
+        self.start = 0;
+    }
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::Buffer for Buffer {
+    #[allow(clippy::arithmetic_side_effects)]
+    fn append(&mut self, chunk: &[u8]) {
+        let would_grow = self.data.len() + chunk.len() > self.data.capacity();
+        if would_grow && self.should_compact() {
+            self.compact();
+        }
+        self.data.extend_from_slice(chunk)
+    }
+
+    fn view(&self) -> &[u8] {
+        self.data.get(self.start..).unwrap_or_default()
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn advance(&mut self, bytes: usize) {
+        let len = self.len();
+        assert!(
+            bytes <= len,
+            "cannot advance past the end of the buffer: {bytes} > {len}"
+        );
+        if bytes == len {
+            self.clear();
+            return;
+        }
+        self.start += bytes;
+        if self.start >= self.compaction_threshold && self.start >= len - bytes {
+            self.compact();
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn len(&self) -> usize {
+        self.data.len() - self.start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer as _;
+
+    #[test]
+    fn test_append_advance() {
+        let mut buf = Buffer::with_compaction_threshold(4);
+
+        buf.append(b"hello");
+        buf.append(b" world");
+        assert_eq!(buf.view(), b"hello world");
+
+        buf.advance(2);
+        assert_eq!(buf.view(), b"llo world");
+        assert_eq!(buf.len(), 9);
+
+        buf.advance(6);
+        assert_eq!(buf.view(), b"rld");
+
+        buf.append(b"!");
+        assert_eq!(buf.view(), b"rld!");
+
+        buf.advance(4);
+        assert!(buf.is_empty());
+        assert_eq!(buf.view(), b"");
+    }
+
+    #[test]
+    fn test_compacts_instead_of_growing() {
+        let mut buf = Buffer::with_compaction_threshold(usize::MAX);
+
+        buf.append(&[0; 64]);
+        let capacity = buf.capacity();
+        buf.advance(60);
+
+        buf.append(&vec![1; capacity - 4]);
+        assert_eq!(buf.capacity(), capacity);
+        assert_eq!(&buf.view()[..4], &[0; 4]);
+        assert_eq!(buf.len(), capacity);
+    }
+
+    #[test]
+    fn test_grows_over_small_prefix() {
+        let mut buf = Buffer::with_compaction_threshold(usize::MAX);
+
+        buf.append(&[0; 64]);
+        let capacity = buf.capacity();
+        buf.advance(1);
+
+        buf.append(&vec![1; capacity]);
+        assert!(buf.capacity() > capacity);
+        assert_eq!(buf.start, 1);
+        assert_eq!(buf.len(), 63 + capacity);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_advance_past_end() {
+        let mut buf = Buffer::new();
+        buf.append(b"abc");
+        buf.advance(4);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_memory_usage() {
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        struct TestObject {
+            pub field: String,
+        }
+
+        let mut dec = crate::State::new(
+            crate::decoder::serde_json::Decoder::<TestObject>::new(),
+            Buffer::new(),
+        );
+
+        let chunk = br#"{
+            "field": "val0"
+        }"#;
+        let mut chunks = chunk.iter().cycle();
+
+        let max_chunks_per_iter = 15;
+        let mut compactions = 0;
+
+        // Simulate processing a huge number of items.
+        for _ in 0..100_000 {
+            // Take random amout of bytes from the chunks iter and prepare the
+            // next chunk.
+            let to_take = rand::random::<usize>() % (chunk.len() * max_chunks_per_iter);
+            let next_chunk = (&mut chunks).take(to_take).copied().collect::<Box<_>>();
+
+            // Process the chunk data.
+            let start_before = dec.buffer.start;
+            let stream = dec.process_next_chunk(next_chunk.as_ref());
+            stream.try_drain().unwrap(); // consume all the emitted items
+
+            // The consumed prefix is only kept while it is small compared to
+            // the threshold or to the live data.
+            let buffer = &dec.buffer;
+            assert!(
+                buffer.start < buffer.compaction_threshold || buffer.start < buffer.len(),
+                "consumed prefix of {} bytes was not compacted, live data is {} bytes",
+                buffer.start,
+                buffer.len(),
+            );
+            if buffer.start < start_before && !buffer.is_empty() {
+                compactions += 1;
+            }
+        }
+        assert!(compactions > 0, "the buffer was never compacted");
+
+        // Check that `buffer` capacity didn't grow out way of hand.
+        let actual_capacity = dec.buffer.capacity();
+        let estimated_capacity_upper_bound = chunk.len() * 100 + DEFAULT_COMPACTION_THRESHOLD * 2;
+        assert!(actual_capacity <= estimated_capacity_upper_bound,
+            "actual capacity {actual_capacity} is over the estimated upper bound of {estimated_capacity_upper_bound}");
+    }
+}
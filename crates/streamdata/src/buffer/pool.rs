@@ -0,0 +1,454 @@
+//! Pooled buffers sharing storage and a global memory budget.
+//!
+//! Useful when running a lot of concurrent [`crate::State`]s: the storage is
+//! only held by a [`Buffer`] while it has some data in it, and is returned to
+//! the [`Pool`] for reuse once the buffer is drained.
+//!
+//! The [`Pool`] also keeps track of the storage capacity held across all of
+//! its buffers, and enforces the global budget on it: [`Buffer::try_append`]
+//! fails and [`crate::Buffer::append`] panics if appending the data would
+//! grow the storage past the budget; [`Pool::available`] can be used to apply
+//! backpressure (i.e. to stop reading) before the budget is exhausted.
+//! The idle storages are shrunk to an equal share of the budget when returned
+//! to the pool, so the memory they hold never exceeds the budget either.
+//!
+//! ```
+//! use streamdata::buffer::pool::Pool;
+//!
+//! # fn example<Decoder>(decoder: Decoder, chunk: &[u8]) -> Result<(), Box<dyn std::error::Error>>
+//! # where
+//! #     Decoder: streamdata::Decoder<streamdata::buffer::pool::Buffer>,
+//! # {
+//! let pool = Pool::new(64 * 1024 * 1024);
+//! let mut state = streamdata::State::new(decoder, pool.buffer());
+//!
+//! state.buffer.try_append(chunk)?;
+//! for value in state.available() {
+//!     // ...
+//! #   let _ = value;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use super::compacting;
+
+/// The default value for the max amount of idle storages kept in the pool.
+pub const DEFAULT_MAX_IDLE: usize = 1024;
+
+/// A shared pool of buffer storage with a global byte budget.
+///
+/// Cloning the pool is cheap, and the clones refer to the same pool.
+#[derive(Debug, Clone)]
+pub struct Pool {
+    /// The shared pool state.
+    shared: Arc<Shared>,
+}
+
+/// The shared pool state.
+#[derive(Debug)]
+struct Shared {
+    /// The storages that are not currently in use.
+    idle: Mutex<Vec<compacting::Buffer>>,
+    /// The max amount of idle storages to keep.
+    max_idle: usize,
+    /// The max amount of storage bytes to be held across all the buffers.
+    budget: usize,
+    /// The amount of storage bytes currently held across all the buffers.
+    used: AtomicUsize,
+}
+
+impl Pool {
+    /// Create a new [`Pool`] with the given budget.
+    pub fn new(budget: usize) -> Self {
+        Self::with_max_idle(budget, DEFAULT_MAX_IDLE)
+    }
+
+    /// Create a new [`Pool`] with the given budget and the max amount of idle
+    /// storages to keep.
+    pub fn with_max_idle(budget: usize, max_idle: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                idle: Mutex::new(Vec::new()),
+                max_idle,
+                budget,
+                used: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Create a new empty [`Buffer`] that uses this pool.
+    pub fn buffer(&self) -> Buffer {
+        Buffer {
+            pool: self.clone(),
+            storage: None,
+            len: 0,
+            capacity: 0,
+        }
+    }
+
+    /// The max amount of storage bytes to be held across all the buffers.
+    pub fn budget(&self) -> usize {
+        self.shared.budget
+    }
+
+    /// The amount of storage bytes currently held across all the buffers.
+    ///
+    /// This is the capacity of the storages in use, so it is at least
+    /// the amount of bytes buffered.
+    pub fn used(&self) -> usize {
+        self.shared.used.load(Ordering::Acquire)
+    }
+
+    /// The amount of storage bytes that can still be allocated before
+    /// exhausting the budget.
+    pub fn available(&self) -> usize {
+        self.budget().saturating_sub(self.used())
+    }
+
+    /// The amount of idle storages currently kept in the pool.
+    pub fn idle(&self) -> usize {
+        self.lock_idle().len()
+    }
+
+    /// The total capacity of the idle storages currently kept in the pool.
+    pub fn idle_capacity(&self) -> usize {
+        self.lock_idle()
+            .iter()
+            .map(compacting::Buffer::capacity)
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Lock the idle storages list.
+    fn lock_idle(&self) -> std::sync::MutexGuard<'_, Vec<compacting::Buffer>> {
+        // The list is always in a consistent state, so it is fine to ignore
+        // the poisoning.
+        self.shared
+            .idle
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Take an idle storage, or allocate a new one.
+    fn acquire(&self) -> compacting::Buffer {
+        self.lock_idle().pop().unwrap_or_default()
+    }
+
+    /// Return the storage to the pool, shrinking it to its share of
+    /// the budget.
+    fn release(&self, mut storage: compacting::Buffer) {
+        storage.clear();
+        let max_capacity = self
+            .shared
+            .budget
+            .checked_div(self.shared.max_idle)
+            .unwrap_or_default();
+        if storage.capacity() > max_capacity {
+            storage.shrink_to(max_capacity);
+        }
+        let mut idle = self.lock_idle();
+        if idle.len() < self.shared.max_idle {
+            idle.push(storage);
+        }
+    }
+
+    /// Reserve the given amount of bytes within the budget.
+    fn reserve(&self, bytes: usize) -> Result<(), BudgetExceeded> {
+        let budget = self.shared.budget;
+        self.shared
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(bytes).filter(|&total| total <= budget)
+            })
+            .map(|_| ())
+            .map_err(|used| BudgetExceeded {
+                requested: bytes,
+                available: budget.saturating_sub(used),
+            })
+    }
+}
+
+/// The error indicating that appending the data would exceed the budget of
+/// the [`Pool`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    /// The amount of storage bytes requested.
+    pub requested: usize,
+    /// The amount of bytes that was available in the budget.
+    pub available: usize,
+}
+
+impl std::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "buffer pool budget exceeded: {} bytes requested, {} bytes available",
+            self.requested, self.available
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// A [`crate::Buffer`] that borrows its storage from the [`Pool`] while it
+/// holds any data.
+#[derive(Debug)]
+pub struct Buffer {
+    /// The pool this buffer belongs to.
+    pool: Pool,
+    /// The storage, present only while there is data in the buffer.
+    storage: Option<compacting::Buffer>,
+    /// The amount of bytes in the buffer.
+    len: usize,
+    /// The capacity of the storage, as accounted in the pool.
+    capacity: usize,
+}
+
+impl Buffer {
+    /// The pool this buffer belongs to.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Append the data to the end of the buffer, unless growing the storage
+    /// to fit it would exceed the budget of the [`Pool`].
+    ///
+    /// The storage grows geometrically while the budget allows it, and only
+    /// to exactly fit the data once the budget gets tight.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn try_append(&mut self, chunk: &[u8]) -> Result<(), BudgetExceeded> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        let pool = &self.pool;
+        let storage = self.storage.get_or_insert_with(|| pool.acquire());
+
+        let current = storage.capacity();
+        let required = storage.required_capacity(chunk.len());
+        let capacity = if required > current {
+            let affordable = self.capacity.saturating_add(pool.available());
+            required.max(current.saturating_mul(2).min(affordable))
+        } else {
+            current
+        };
+        // An acquired idle storage is not accounted yet, so this covers its
+        // capacity as well.
+        if let Err(err) = pool.reserve(capacity - self.capacity) {
+            if self.len == 0 {
+                if let Some(storage) = self.storage.take() {
+                    pool.release(storage);
+                }
+            }
+            return Err(err);
+        }
+        storage.grow_to(capacity);
+
+        // The allocator is free to give a bit more than requested.
+        let actual = storage.capacity();
+        if actual > capacity {
+            pool.shared
+                .used
+                .fetch_add(actual - capacity, Ordering::AcqRel);
+        }
+        self.capacity = actual;
+
+        crate::Buffer::append(storage, chunk);
+        self.len += chunk.len();
+        Ok(())
+    }
+
+    /// Return the storage to the pool, releasing its capacity from
+    /// the budget.
+    fn release(&mut self) {
+        self.pool
+            .shared
+            .used
+            .fetch_sub(self.capacity, Ordering::AcqRel);
+        self.capacity = 0;
+        if let Some(storage) = self.storage.take() {
+            self.pool.release(storage);
+        }
+    }
+}
+
+impl crate::Buffer for Buffer {
+    /// Append the data to the end of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if growing the storage to fit the data would exceed the budget
+    /// of the [`Pool`]; use [`Buffer::try_append`] to handle that instead.
+    fn append(&mut self, chunk: &[u8]) {
+        if let Err(err) = self.try_append(chunk) {
+            panic!("{err}");
+        }
+    }
+
+    fn view(&self) -> &[u8] {
+        self.storage
+            .as_ref()
+            .map(crate::Buffer::view)
+            .unwrap_or_default()
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn advance(&mut self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        let Some(ref mut storage) = self.storage else {
+            panic!("cannot advance past the end of the buffer: {bytes} > 0");
+        };
+        storage.advance(bytes);
+        self.len -= bytes;
+        if self.len == 0 {
+            self.release();
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer as _;
+
+    #[test]
+    fn test_storage_is_returned_when_empty() {
+        let pool = Pool::new(1024);
+        let mut buf = pool.buffer();
+        assert_eq!(pool.idle(), 0);
+
+        buf.append(b"hello");
+        assert_eq!(pool.used(), 5);
+        assert_eq!(pool.idle(), 0);
+
+        buf.advance(2);
+        assert_eq!(buf.view(), b"llo");
+        assert_eq!(pool.used(), 5);
+
+        buf.advance(3);
+        assert!(buf.is_empty());
+        assert_eq!(pool.used(), 0);
+        assert_eq!(pool.idle(), 1);
+
+        // The storage is reused.
+        let mut other = pool.buffer();
+        other.append(b"world");
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(other.view(), b"world");
+    }
+
+    #[test]
+    fn test_budget() {
+        let pool = Pool::new(10);
+        let mut buf1 = pool.buffer();
+        let mut buf2 = pool.buffer();
+
+        buf1.try_append(b"012345").unwrap();
+        assert_eq!(pool.available(), 4);
+
+        let err = buf2.try_append(b"012345").unwrap_err();
+        assert_eq!(
+            err,
+            BudgetExceeded {
+                requested: 6,
+                available: 4
+            }
+        );
+        assert!(buf2.is_empty());
+
+        buf2.try_append(b"0123").unwrap();
+        assert_eq!(pool.available(), 0);
+
+        drop(buf1);
+        assert_eq!(pool.available(), 6);
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn test_budget_counts_capacity() {
+        let pool = Pool::new(100);
+        let mut buf1 = pool.buffer();
+        let mut buf2 = pool.buffer();
+
+        buf1.try_append(&[0; 60]).unwrap();
+        buf1.advance(50);
+        assert_eq!(buf1.len(), 10);
+        assert_eq!(pool.used(), 60);
+
+        let err = buf2.try_append(&[0; 50]).unwrap_err();
+        assert_eq!(
+            err,
+            BudgetExceeded {
+                requested: 50,
+                available: 40
+            }
+        );
+        assert_eq!(pool.used(), 60);
+
+        // Growing the storage is accounted too, and never goes past
+        // the budget.
+        buf2.try_append(&[0; 20]).unwrap();
+        buf2.try_append(&[0; 20]).unwrap();
+        assert_eq!(pool.used(), 100);
+        buf2.try_append(&[0; 1]).unwrap_err();
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer pool budget exceeded")]
+    fn test_append_over_budget() {
+        let pool = Pool::new(10);
+        let mut buf = pool.buffer();
+
+        buf.append(b"0123456789");
+        buf.append(b"a");
+    }
+
+    #[test]
+    fn test_max_idle() {
+        let pool = Pool::with_max_idle(1024, 1);
+        let mut buf1 = pool.buffer();
+        let mut buf2 = pool.buffer();
+
+        buf1.append(b"a");
+        buf2.append(b"b");
+        drop(buf1);
+        drop(buf2);
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(pool.used(), 0);
+    }
+
+    #[test]
+    fn test_idle_capacity() {
+        let pool = Pool::with_max_idle(2048, 64);
+        let mut buf1 = pool.buffer();
+        let mut buf2 = pool.buffer();
+
+        buf1.append(&[0; 1000]);
+        buf2.append(&[0; 10]);
+        drop(buf1);
+        drop(buf2);
+        assert_eq!(pool.idle(), 2);
+        assert!(pool.idle_capacity() <= 64, "{}", pool.idle_capacity());
+
+        // The small storage is kept as is.
+        let mut buf = pool.buffer();
+        buf.append(b"a");
+        assert!(buf.storage.as_ref().unwrap().capacity() >= 10);
+    }
+}
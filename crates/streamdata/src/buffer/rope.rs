@@ -0,0 +1,387 @@
+//! Zero-copy rope buffer built from [`bytes::Bytes`] segments.
+
+use std::{collections::VecDeque, sync::OnceLock};
+
+/// A [`crate::Buffer`] that stores the incoming [`bytes::Bytes`] as separate
+/// segments without copying them.
+///
+/// The data is only made contiguous when [`crate::Buffer::view`] is called
+/// while the buffer holds more than one segment; the joined data then
+/// replaces the segments upon the next mutation, and the segments pushed
+/// after that are appended to it, until the buffer is advanced.
+/// This way only the new data is copied, and a large incomplete value is not
+/// joined over and over again as more data arrives.
+/// Once the buffer is advanced, the rest of the joined data becomes a single
+/// segment again (without copying), and the new segments are stored as is.
+/// Decoders that can work with segmented input should use [`Buffer::buf`]
+/// instead, which never copies.
+///
+/// Use [`crate::State::process_next_buf`] to feed the data to this buffer;
+/// [`crate::State::process_next_chunk`] has to copy the chunk.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    /// The previously joined data, preceding the segments.
+    ///
+    /// While this is not empty, there are no segments, and the new data is
+    /// appended here until the buffer is advanced.
+    joined: bytes::BytesMut,
+    /// The data segments, in order.
+    segments: VecDeque<bytes::Bytes>,
+    /// The total amount of bytes across all the segments.
+    len: usize,
+    /// The joined contents of all the segments, populated on demand.
+    contiguous: OnceLock<bytes::BytesMut>,
+}
+
+impl Buffer {
+    /// Create a new empty [`Buffer`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the given segment to the end of the buffer without copying.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn push(&mut self, segment: bytes::Bytes) {
+        self.collapse();
+        if segment.is_empty() {
+            return;
+        }
+        self.len += segment.len();
+        if self.joined.is_empty() {
+            self.segments.push_back(segment);
+        } else {
+            self.joined.extend_from_slice(&segment);
+        }
+    }
+
+    /// A [`bytes::Buf`] view of the current contents of the buffer that
+    /// walks over the segments without copying.
+    pub fn buf(&self) -> Segments<'_> {
+        Segments {
+            joined: &self.joined,
+            segments: &self.segments,
+            index: 0,
+            offset: 0,
+            remaining: self.len,
+        }
+    }
+
+    /// The amount of segments the data is currently split into.
+    pub fn segments_count(&self) -> usize {
+        usize::from(!self.joined.is_empty()).saturating_add(self.segments.len())
+    }
+
+    /// Replace the segments with the joined contents, if they were joined.
+    fn collapse(&mut self) {
+        if let Some(contiguous) = self.contiguous.take() {
+            self.segments.clear();
+            self.joined = contiguous;
+        }
+    }
+}
+
+impl crate::Buffer for Buffer {
+    fn append(&mut self, chunk: &[u8]) {
+        self.push(bytes::Bytes::copy_from_slice(chunk))
+    }
+
+    fn view(&self) -> &[u8] {
+        if !self.joined.is_empty() {
+            return &self.joined;
+        }
+        match self.segments.len() {
+            0 => &[],
+            1 => &self.segments[0],
+            _ => self.contiguous.get_or_init(|| {
+                let mut joined = bytes::BytesMut::with_capacity(self.len);
+                for segment in &self.segments {
+                    joined.extend_from_slice(segment);
+                }
+                joined
+            }),
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn advance(&mut self, mut bytes: usize) {
+        assert!(
+            bytes <= self.len,
+            "cannot advance past the end of the buffer: {bytes} > {}",
+            self.len
+        );
+        self.collapse();
+        self.len -= bytes;
+        if !self.joined.is_empty() {
+            // Get back to storing the segments; the rest of the joined data
+            // is kept as is, and is released once consumed.
+            let mut joined = std::mem::take(&mut self.joined).freeze();
+            bytes::Buf::advance(&mut joined, bytes);
+            if !joined.is_empty() {
+                self.segments.push_back(joined);
+            }
+            return;
+        }
+        while bytes > 0 {
+            let Some(front) = self.segments.front_mut() else {
+                break;
+            };
+            if front.len() > bytes {
+                bytes::Buf::advance(front, bytes);
+                break;
+            }
+            bytes -= front.len();
+            self.segments.pop_front();
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn append_buf<B>(&mut self, mut buf: B)
+    where
+        B: bytes::Buf,
+    {
+        let remaining = buf.remaining();
+        self.push(buf.copy_to_bytes(remaining))
+    }
+}
+
+/// A [`bytes::Buf`] over the segments of the [`Buffer`].
+///
+/// Advancing this view does not affect the [`Buffer`] itself.
+#[derive(Debug, Clone)]
+pub struct Segments<'a> {
+    /// The remaining part of the joined data of the buffer.
+    joined: &'a [u8],
+    /// The segments of the buffer.
+    segments: &'a VecDeque<bytes::Bytes>,
+    /// The index of the current segment.
+    index: usize,
+    /// The offset in the current segment.
+    offset: usize,
+    /// The total amount of bytes remaining.
+    remaining: usize,
+}
+
+impl<'a> bytes::Buf for Segments<'a> {
+    fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    fn chunk(&self) -> &[u8] {
+        if !self.joined.is_empty() {
+            return self.joined;
+        }
+        self.segments
+            .get(self.index)
+            .and_then(|segment| segment.get(self.offset..))
+            .unwrap_or_default()
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn advance(&mut self, mut cnt: usize) {
+        assert!(
+            cnt <= self.remaining,
+            "cannot advance past the end of the buffer: {cnt} > {}",
+            self.remaining
+        );
+        self.remaining -= cnt;
+        let joined = cnt.min(self.joined.len());
+        self.joined = &self.joined[joined..];
+        cnt -= joined;
+        while cnt > 0 {
+            let segment_left = self.segments[self.index].len() - self.offset;
+            if segment_left > cnt {
+                self.offset += cnt;
+                break;
+            }
+            cnt -= segment_left;
+            self.index += 1;
+            self.offset = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer as _;
+    use bytes::Buf as _;
+
+    #[test]
+    fn test_append_buf_does_not_copy() {
+        let mut buf = Buffer::new();
+
+        let data = bytes::Bytes::from_static(b"hello world");
+        buf.append_buf(data.clone());
+        assert_eq!(buf.view().as_ptr(), data.as_ptr());
+
+        buf.advance(6);
+        assert_eq!(buf.view(), b"world");
+        assert_eq!(buf.view().as_ptr(), data[6..].as_ptr());
+    }
+
+    #[test]
+    fn test_view_joins_segments() {
+        let mut buf = Buffer::new();
+
+        buf.push(bytes::Bytes::from_static(b"hel"));
+        buf.push(bytes::Bytes::from_static(b""));
+        buf.push(bytes::Bytes::from_static(b"lo"));
+        assert_eq!(buf.segments_count(), 2);
+        assert_eq!(buf.len(), 5);
+        assert_eq!(buf.view(), b"hello");
+
+        // The joined data replaces the segments, and the new data is
+        // appended to it.
+        buf.push(bytes::Bytes::from_static(b"!"));
+        assert_eq!(buf.segments_count(), 1);
+        assert_eq!(buf.view(), b"hello!");
+        let mut view = buf.buf();
+        assert_eq!(view.chunk(), b"hello!");
+        view.advance(6);
+        assert!(!view.has_remaining());
+    }
+
+    #[test]
+    fn test_joined_data_is_consumed() {
+        let mut buf = Buffer::new();
+
+        buf.push(bytes::Bytes::from_static(b"abc"));
+        buf.push(bytes::Bytes::from_static(b"def"));
+        assert_eq!(buf.view(), b"abcdef");
+        buf.push(bytes::Bytes::from_static(b"ghi"));
+
+        buf.advance(4);
+        assert_eq!(buf.segments_count(), 1);
+        assert_eq!(buf.view(), b"efghi");
+
+        // The new segments are not appended to the rest of the joined data.
+        let data = bytes::Bytes::from_static(b"jkl");
+        buf.push(data.clone());
+        assert_eq!(buf.segments_count(), 2);
+        let mut view = buf.buf();
+        view.advance(5);
+        assert_eq!(view.chunk().as_ptr(), data.as_ptr());
+
+        buf.advance(8);
+        assert!(buf.is_empty());
+
+        // Back to storing the segments without copying.
+        let data = bytes::Bytes::from_static(b"jkl");
+        buf.push(data.clone());
+        buf.push(bytes::Bytes::from_static(b"mno"));
+        assert_eq!(buf.segments_count(), 2);
+        assert_eq!(buf.buf().chunk().as_ptr(), data.as_ptr());
+    }
+
+    #[test]
+    fn test_large_value_is_not_rejoined() {
+        let mut buf = Buffer::new();
+
+        buf.push(bytes::Bytes::from_static(b"a"));
+        buf.push(bytes::Bytes::from_static(b"b"));
+        assert_eq!(buf.view(), b"ab");
+        let mut capacity_changes = 0;
+        let mut capacity = buf.joined.capacity();
+        for _ in 0..10_000 {
+            buf.push(bytes::Bytes::from_static(b"c"));
+            assert_eq!(buf.segments_count(), 1);
+            assert_eq!(buf.view().last(), Some(&b'c'));
+            if buf.joined.capacity() != capacity {
+                capacity = buf.joined.capacity();
+                capacity_changes += 1;
+            }
+        }
+        assert_eq!(buf.len(), 10_002);
+        // The joined data grows geometrically rather than being copied on
+        // every push.
+        assert!(capacity_changes < 20, "{capacity_changes}");
+    }
+
+    #[test]
+    fn test_sync() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<Buffer>();
+    }
+
+    #[test]
+    fn test_advance_across_segments() {
+        let mut buf = Buffer::new();
+
+        buf.push(bytes::Bytes::from_static(b"abc"));
+        buf.push(bytes::Bytes::from_static(b"def"));
+        buf.push(bytes::Bytes::from_static(b"ghi"));
+
+        buf.advance(4);
+        assert_eq!(buf.segments_count(), 2);
+        assert_eq!(buf.len(), 5);
+        assert_eq!(buf.view(), b"efghi");
+
+        buf.advance(5);
+        assert!(buf.is_empty());
+        assert_eq!(buf.segments_count(), 0);
+    }
+
+    #[test]
+    fn test_buf_view() {
+        let mut buf = Buffer::new();
+
+        buf.push(bytes::Bytes::from_static(b"abc"));
+        buf.push(bytes::Bytes::from_static(b"def"));
+
+        let mut view = buf.buf();
+        assert_eq!(view.remaining(), 6);
+        assert_eq!(view.chunk(), b"abc");
+        view.advance(2);
+        assert_eq!(view.chunk(), b"c");
+        view.advance(2);
+        assert_eq!(view.chunk(), b"ef");
+        assert_eq!(view.copy_to_bytes(2), &b"ef"[..]);
+        assert!(!view.has_remaining());
+
+        // The buffer itself is not affected.
+        assert_eq!(buf.len(), 6);
+        assert_eq!(buf.segments_count(), 2);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_decoding() {
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        struct TestObject {
+            pub field: String,
+        }
+
+        let mut dec = crate::State::new(
+            crate::decoder::serde_json::Decoder::<TestObject>::new(),
+            Buffer::new(),
+        );
+
+        {
+            let mut stream = dec.process_next_buf(bytes::Bytes::from_static(br#"{"fie"#));
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let stream = dec.process_next_buf(bytes::Bytes::from_static(
+                br#"ld": "val0"}{"field": "val1"}"#,
+            ));
+            let values: Vec<_> = stream.try_collect().unwrap();
+            assert_eq!(
+                values,
+                [
+                    TestObject {
+                        field: "val0".into()
+                    },
+                    TestObject {
+                        field: "val1".into()
+                    }
+                ]
+            );
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+}
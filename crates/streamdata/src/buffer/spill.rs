@@ -0,0 +1,333 @@
+//! Spill-to-disk buffer for oversized partial frames.
+
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::compacting;
+
+/// A [`crate::Buffer`] that keeps the data in memory while it is small, and
+/// moves it to a memory-mapped temporary file once it grows past
+/// the threshold.
+///
+/// The file is anonymous (unlinked right after creation), and is dropped
+/// once the buffered data shrinks back to half the threshold, at which point
+/// the buffer returns to memory.
+///
+/// If anything goes wrong with the file, the buffer falls back to keeping
+/// the data in memory, and does not try to spill it again until the data
+/// shrinks back to half the threshold.
+#[derive(Debug)]
+pub struct Buffer {
+    /// The current storage.
+    storage: Storage,
+    /// The amount of bytes past which the data is spilled to disk.
+    threshold: usize,
+    /// The directory to create the temporary files in; the system default
+    /// temporary directory is used if not set.
+    temp_dir: Option<PathBuf>,
+    /// Whether spilling has failed for the data currently in memory.
+    spill_failed: bool,
+}
+
+/// The storage of the [`Buffer`].
+#[derive(Debug)]
+enum Storage {
+    /// The data is in memory.
+    Memory(compacting::Buffer),
+    /// The data is in a memory-mapped file.
+    File(Spilled),
+}
+
+/// The memory-mapped file storage.
+#[derive(Debug)]
+struct Spilled {
+    /// The file backing the map.
+    file: File,
+    /// The mapping of the whole file.
+    map: memmap2::MmapMut,
+    /// The offset of the first live byte in the map.
+    start: usize,
+    /// The offset right after the last live byte in the map.
+    end: usize,
+}
+
+impl Buffer {
+    /// Create a new empty [`Buffer`] with the given threshold.
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            storage: Storage::Memory(compacting::Buffer::new()),
+            threshold,
+            temp_dir: None,
+            spill_failed: false,
+        }
+    }
+
+    /// Create the temporary files in the given directory.
+    pub fn with_temp_dir(mut self, temp_dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = Some(temp_dir.into());
+        self
+    }
+
+    /// The amount of bytes past which the data is spilled to disk.
+    pub const fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns `true` if the data currently resides in a file.
+    pub const fn is_spilled(&self) -> bool {
+        matches!(self.storage, Storage::File(_))
+    }
+
+    /// Move the data to a file, appending the given chunk.
+    fn spill(temp_dir: Option<&Path>, data: &[u8], chunk: &[u8]) -> io::Result<Spilled> {
+        let file = match temp_dir {
+            Some(temp_dir) => tempfile::tempfile_in(temp_dir)?,
+            None => tempfile::tempfile()?,
+        };
+        let mut spilled = Spilled {
+            map: map(&file)?,
+            file,
+            start: 0,
+            end: 0,
+        };
+        spilled.append(data)?;
+        spilled.append(chunk)?;
+        Ok(spilled)
+    }
+
+    /// Move the data back to memory.
+    fn unspill(data: &[u8]) -> Storage {
+        let mut buffer = compacting::Buffer::new();
+        crate::Buffer::append(&mut buffer, data);
+        Storage::Memory(buffer)
+    }
+}
+
+impl Spilled {
+    /// The amount of live bytes.
+    #[allow(clippy::arithmetic_side_effects)]
+    const fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// The live data.
+    fn view(&self) -> &[u8] {
+        self.map.get(self.start..self.end).unwrap_or_default()
+    }
+
+    /// Append the data at the end, growing the file if needed.
+    #[allow(clippy::arithmetic_side_effects)]
+    fn append(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if self.end + chunk.len() > self.map.len() {
+            // Compact.
+            self.map.copy_within(self.start..self.end, 0);
+            self.end = self.len();
+            self.start = 0;
+        }
+        let new_end = self.end + chunk.len();
+        if new_end > self.map.len() {
+            let capacity = new_end
+                .checked_next_power_of_two()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::OutOfMemory, "capacity overflow"))?;
+            self.file
+                .set_len(u64::try_from(capacity).map_err(io::Error::other)?)?;
+            self.map = map(&self.file)?;
+        }
+        self.map[self.end..new_end].copy_from_slice(chunk);
+        self.end = new_end;
+        Ok(())
+    }
+}
+
+/// Map the whole file into memory, ensuring it is not empty.
+///
+/// This is the only place in the crate that requires `unsafe`.
+#[allow(unsafe_code)]
+fn map(file: &File) -> io::Result<memmap2::MmapMut> {
+    if file.metadata()?.len() == 0 {
+        file.set_len(4096)?;
+    }
+    // SAFETY: the map is only sound while nothing truncates or writes to
+    // the file behind it. The file is unlinked right upon creation, so it
+    // cannot be opened by path, and the [`Spilled`] storage is the only
+    // handle to it in this process; the storage itself only ever grows
+    // the file, replacing the map right after. The file can still be reached
+    // from the outside through `/proc/<pid>/fd` (or the platform equivalent),
+    // but only by someone privileged enough to inspect this process, who can
+    // equally modify its memory directly (e.g. through `/proc/<pid>/mem`), so
+    // the map adds no new way to break the invariants from the outside.
+    unsafe { memmap2::MmapMut::map_mut(file) }
+}
+
+impl crate::Buffer for Buffer {
+    #[allow(clippy::arithmetic_side_effects)]
+    fn append(&mut self, chunk: &[u8]) {
+        let result = match self.storage {
+            Storage::Memory(ref mut buffer) => {
+                if self.spill_failed || buffer.len() + chunk.len() <= self.threshold {
+                    buffer.append(chunk);
+                    return;
+                }
+                Self::spill(self.temp_dir.as_deref(), buffer.view(), chunk).map(Storage::File)
+            }
+            Storage::File(ref mut spilled) => match spilled.append(chunk) {
+                Ok(()) => return,
+                Err(err) => Err(err),
+            },
+        };
+        self.storage = result.unwrap_or_else(|err| {
+            tracing::warn!(
+                %err,
+                "unable to spill the buffer to disk, keeping the data in memory"
+            );
+            self.spill_failed = true;
+            let mut buffer = compacting::Buffer::new();
+            buffer.append(self.view());
+            buffer.append(chunk);
+            Storage::Memory(buffer)
+        });
+    }
+
+    fn view(&self) -> &[u8] {
+        match self.storage {
+            Storage::Memory(ref buffer) => buffer.view(),
+            Storage::File(ref spilled) => spilled.view(),
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn advance(&mut self, bytes: usize) {
+        match self.storage {
+            Storage::Memory(ref mut buffer) => {
+                buffer.advance(bytes);
+                if buffer.len() <= self.threshold / 2 {
+                    self.spill_failed = false;
+                }
+            }
+            Storage::File(ref mut spilled) => {
+                assert!(
+                    bytes <= spilled.len(),
+                    "cannot advance past the end of the buffer: {bytes} > {}",
+                    spilled.len()
+                );
+                spilled.start += bytes;
+                if spilled.len() <= self.threshold / 2 {
+                    self.storage = Self::unspill(spilled.view());
+                }
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self.storage {
+            Storage::Memory(ref buffer) => buffer.len(),
+            Storage::File(ref spilled) => spilled.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer as _;
+
+    #[test]
+    fn test_spill_and_return() {
+        let mut buf = Buffer::new(8);
+
+        buf.append(b"0123");
+        assert!(!buf.is_spilled());
+
+        buf.append(b"4567");
+        assert!(!buf.is_spilled());
+
+        buf.append(b"89");
+        assert!(buf.is_spilled());
+        assert_eq!(buf.view(), b"0123456789");
+
+        buf.advance(3);
+        assert!(buf.is_spilled());
+        assert_eq!(buf.view(), b"3456789");
+
+        buf.advance(3);
+        assert!(!buf.is_spilled());
+        assert_eq!(buf.view(), b"6789");
+    }
+
+    #[test]
+    fn test_grow() {
+        let mut buf = Buffer::new(16);
+
+        let data = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        for chunk in data.chunks(1000) {
+            buf.append(chunk);
+        }
+        assert!(buf.is_spilled());
+        assert_eq!(buf.len(), data.len());
+        assert_eq!(buf.view(), data);
+
+        buf.advance(50_000);
+        buf.append(&data[..10]);
+        assert_eq!(&buf.view()[..50_000], &data[50_000..]);
+        assert_eq!(&buf.view()[50_000..], &data[..10]);
+    }
+
+    #[test]
+    fn test_spill_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let spill_dir = temp_dir.path().join("spill");
+        let mut buf = Buffer::new(4).with_temp_dir(&spill_dir);
+
+        buf.append(b"0123");
+        buf.append(b"45");
+        assert!(!buf.is_spilled());
+        assert_eq!(buf.view(), b"012345");
+
+        // Not retried until the data shrinks.
+        std::fs::create_dir(&spill_dir).unwrap();
+        buf.append(b"67");
+        assert!(!buf.is_spilled());
+        buf.advance(4);
+        buf.append(b"89");
+        assert!(!buf.is_spilled());
+        assert_eq!(buf.view(), b"456789");
+
+        buf.advance(4);
+        buf.append(b"abc");
+        assert!(buf.is_spilled());
+        assert_eq!(buf.view(), b"89abc");
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_decoding() {
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        struct TestObject {
+            pub field: String,
+        }
+
+        let mut dec = crate::State::new(
+            crate::decoder::serde_json::Decoder::<TestObject>::new(),
+            Buffer::new(64),
+        );
+
+        let long_value = "x".repeat(10_000);
+        let document = format!(r#"{{"field": "{long_value}"}}{{"field": "val1"}}{{"#);
+
+        let mut spilled = false;
+        for chunk in document.as_bytes().chunks(100) {
+            for value in dec.process_next_chunk(chunk) {
+                let value = value.unwrap();
+                assert!(value.field == long_value || value.field == "val1");
+            }
+            spilled |= dec.buffer.is_spilled();
+        }
+
+        assert!(spilled, "the long value was never spilled to disk");
+        assert!(!dec.buffer.is_spilled());
+        assert_eq!(dec.finish().unwrap_err().view(), b"{");
+    }
+}
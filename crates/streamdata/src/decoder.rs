@@ -1,8 +1,26 @@
 //! Decoder implementations.
 
+pub mod checksum;
+#[cfg(feature = "ciborium")]
+pub mod ciborium;
+pub mod dynamic;
+pub mod ext;
+mod framing;
+#[cfg(any(feature = "serde_json", feature = "k8s-openapi"))]
+mod json_scanner;
 #[cfg(feature = "k8s-openapi")]
 pub mod k8s_openapi;
+pub mod layered;
+#[cfg(feature = "bytes")]
+pub mod raw;
+#[cfg(feature = "rmp-serde")]
+pub mod rmp_serde;
 #[cfg(feature = "serde_json")]
 pub mod serde_json;
+#[cfg(feature = "serde_json")]
+pub mod sniff;
+#[cfg(test)]
+mod test_util;
 #[cfg(feature = "tokio-util")]
 pub mod tokio_util;
+pub mod two_phase;
@@ -0,0 +1,518 @@
+//! Checksum verification of the frames produced by another decoder.
+
+/// The checksum algorithm.
+pub trait Algorithm {
+    /// The size of the checksum in the frame, in bytes, up to 8.
+    const SIZE: usize;
+
+    /// Compute the checksum of the given data.
+    fn checksum(&self, data: &[u8]) -> u64;
+}
+
+/// CRC-32 (IEEE), as used by zlib, gzip and the like.
+#[cfg(feature = "crc32")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crc32;
+
+#[cfg(feature = "crc32")]
+impl Algorithm for Crc32 {
+    const SIZE: usize = 4;
+
+    fn checksum(&self, data: &[u8]) -> u64 {
+        crc32fast::hash(data).into()
+    }
+}
+
+/// CRC-32C (Castagnoli), as used by iSCSI, ext4 and the like.
+#[cfg(feature = "crc32c")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crc32c;
+
+#[cfg(feature = "crc32c")]
+impl Algorithm for Crc32c {
+    const SIZE: usize = 4;
+
+    fn checksum(&self, data: &[u8]) -> u64 {
+        crc32c::crc32c(data).into()
+    }
+}
+
+/// XXH32 with the given seed.
+#[cfg(feature = "xxhash")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XxHash32 {
+    /// The seed.
+    pub seed: u32,
+}
+
+#[cfg(feature = "xxhash")]
+impl Algorithm for XxHash32 {
+    const SIZE: usize = 4;
+
+    fn checksum(&self, data: &[u8]) -> u64 {
+        xxhash_rust::xxh32::xxh32(data, self.seed).into()
+    }
+}
+
+/// XXH64 with the given seed.
+#[cfg(feature = "xxhash")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XxHash64 {
+    /// The seed.
+    pub seed: u64,
+}
+
+#[cfg(feature = "xxhash")]
+impl Algorithm for XxHash64 {
+    const SIZE: usize = 8;
+
+    fn checksum(&self, data: &[u8]) -> u64 {
+        xxhash_rust::xxh64::xxh64(data, self.seed)
+    }
+}
+
+/// Where the checksum is located in the frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Position {
+    /// The checksum precedes the payload.
+    Leading,
+    /// The checksum follows the payload.
+    #[default]
+    Trailing,
+}
+
+/// The byte order of the checksum in the frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Big-endian, i.e. the network byte order.
+    #[default]
+    BigEndian,
+    /// Little-endian.
+    LittleEndian,
+}
+
+impl ByteOrder {
+    /// Read the number from the given bytes.
+    fn read(self, bytes: &[u8]) -> u64 {
+        let fold = |acc: u64, byte: &u8| acc << 8 | u64::from(*byte);
+        match self {
+            Self::BigEndian => bytes.iter().fold(0, fold),
+            Self::LittleEndian => bytes.iter().rev().fold(0, fold),
+        }
+    }
+}
+
+/// The frame the checksum can be stripped from.
+pub trait Frame {
+    /// View the contents of the frame.
+    fn bytes(&self) -> &[u8];
+
+    /// Drop the given amounts of bytes from the start and the end of
+    /// the frame.
+    fn strip(&mut self, leading: usize, trailing: usize);
+}
+
+impl Frame for Vec<u8> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn strip(&mut self, leading: usize, trailing: usize) {
+        self.truncate(self.len().saturating_sub(trailing));
+        self.drain(..leading);
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Frame for bytes::BytesMut {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn strip(&mut self, leading: usize, trailing: usize) {
+        self.truncate(self.len().saturating_sub(trailing));
+        bytes::Buf::advance(self, leading);
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Frame for bytes::Bytes {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn strip(&mut self, leading: usize, trailing: usize) {
+        self.truncate(self.len().saturating_sub(trailing));
+        bytes::Buf::advance(self, leading);
+    }
+}
+
+/// The decoder that verifies the checksum of each frame produced by
+/// the inner decoder, and yields the frames with the checksum stripped.
+///
+/// The inner decoder yields the whole frames, checksum included, as
+/// a [`Frame`] (i.e. [`Vec<u8>`] or [`bytes::BytesMut`]); use
+/// [`crate::decoder::ext::DecoderExt::map`] to convert them if needed.
+/// Combine with [`crate::decoder::layered::Decoder`] to decode the payload
+/// of the verified frames.
+///
+/// By default, the checksum is expected at the end of the frame, in
+/// the big-endian byte order.
+/// The corruption is contained within the frame: a frame whose checksum
+/// does not match its contents is reported as [`Error::Mismatch`], and
+/// the verification carries on with the next frame, as the framing itself
+/// is not protected by the checksum.
+/// See [`Decoder::with_skip_mismatched`] to drop the corrupted frames
+/// silently instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoder<Inner, Algorithm> {
+    /// The frame decoder.
+    pub inner: Inner,
+    /// The checksum algorithm.
+    pub algorithm: Algorithm,
+    /// Where the checksum is located in the frame.
+    pub position: Position,
+    /// The byte order of the checksum.
+    pub byte_order: ByteOrder,
+    /// Whether to skip the frames with mismatched checksums instead of
+    /// reporting an error.
+    pub skip_mismatched: bool,
+    /// The counter of the frames.
+    frames: super::framing::Counter,
+}
+
+impl<Inner, Algorithm> Decoder<Inner, Algorithm> {
+    /// Create a new [`Decoder`] with the given frame decoder and checksum
+    /// algorithm.
+    pub const fn new(inner: Inner, algorithm: Algorithm) -> Self {
+        Self {
+            inner,
+            algorithm,
+            position: Position::Trailing,
+            byte_order: ByteOrder::BigEndian,
+            skip_mismatched: false,
+            frames: super::framing::Counter::new(),
+        }
+    }
+
+    /// Set where the checksum is located in the frame.
+    pub const fn with_position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Set the byte order of the checksum.
+    pub const fn with_byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.byte_order = byte_order;
+        self
+    }
+
+    /// Skip the frames with mismatched checksums (via
+    /// [`crate::DecodeError::SkipData`]) instead of reporting an error.
+    ///
+    /// The frames that are too short to hold a checksum are still reported,
+    /// as they indicate a misconfiguration rather than a corruption.
+    pub const fn with_skip_mismatched(mut self, skip_mismatched: bool) -> Self {
+        self.skip_mismatched = skip_mismatched;
+        self
+    }
+
+    /// Verify the checksum of the given frame and strip it.
+    fn verify<F, InnerError>(
+        &mut self,
+        mut frame: F,
+    ) -> Result<F, crate::DecodeError<Error<InnerError>>>
+    where
+        F: Frame,
+        Algorithm: self::Algorithm,
+    {
+        let index = self.frames.next();
+
+        let size = Algorithm::SIZE;
+        let bytes = frame.bytes();
+        let Some(payload_len) = bytes.len().checked_sub(size) else {
+            return Err(crate::DecodeError::Other(Error::TooShort {
+                frame: index,
+                len: bytes.len(),
+            }));
+        };
+
+        let (leading, trailing, checksum, payload) = match self.position {
+            Position::Leading => {
+                let (checksum, payload) = bytes.split_at(size);
+                (size, 0, checksum, payload)
+            }
+            Position::Trailing => {
+                let (payload, checksum) = bytes.split_at(payload_len);
+                (0, size, checksum, payload)
+            }
+        };
+        let expected = self.byte_order.read(checksum);
+        let actual = self.algorithm.checksum(payload);
+
+        if expected != actual {
+            let error = Error::Mismatch {
+                frame: index,
+                expected,
+                actual,
+            };
+            return super::framing::reject(error, self.skip_mismatched, index, "checksum mismatch");
+        }
+
+        frame.strip(leading, trailing);
+        Ok(frame)
+    }
+}
+
+impl<Inner, Algorithm, Buffer> crate::Decoder<Buffer> for Decoder<Inner, Algorithm>
+where
+    Buffer: crate::Buffer,
+    Inner: crate::Decoder<Buffer>,
+    Inner::Value: Frame,
+    Algorithm: self::Algorithm,
+{
+    type Value = Inner::Value;
+    type Error = Error<Inner::Error>;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let frame = self
+            .inner
+            .decode(input)
+            .map_err(|err| super::framing::inner_error(err, Error::Inner))?;
+        self.verify(frame)
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let frame = self
+            .inner
+            .decode_eof(input)
+            .map_err(|err| super::framing::inner_error(err, Error::Inner))?;
+        self.verify(frame)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// An error that can occur while verifying the frames.
+#[derive(Debug)]
+pub enum Error<InnerError> {
+    /// Unable to decode the frame.
+    Inner(InnerError),
+    /// The frame is too short to contain the checksum.
+    TooShort {
+        /// The index of the frame in the stream, starting from zero.
+        frame: usize,
+        /// The length of the frame.
+        len: usize,
+    },
+    /// The checksum in the frame does not match the payload.
+    Mismatch {
+        /// The index of the frame in the stream, starting from zero.
+        frame: usize,
+        /// The checksum stored in the frame.
+        expected: u64,
+        /// The checksum of the payload.
+        actual: u64,
+    },
+}
+
+impl<InnerError> std::fmt::Display for Error<InnerError>
+where
+    InnerError: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Inner(err) => write!(f, "frame: {err}"),
+            Self::TooShort { frame, len } => {
+                write!(
+                    f,
+                    "frame {frame} of {len} bytes is too short for a checksum"
+                )
+            }
+            Self::Mismatch {
+                frame,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch in frame {frame}: expected {expected:#x}, got {actual:#x}"
+            ),
+        }
+    }
+}
+
+impl<InnerError> std::error::Error for Error<InnerError>
+where
+    InnerError: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Inner(err) => Some(err),
+            Self::TooShort { .. } | Self::Mismatch { .. } => None,
+        }
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "tokio-util",
+    feature = "crc32",
+    feature = "crc32c",
+    feature = "xxhash"
+))]
+mod tests {
+    use super::*;
+
+    type Frames = crate::decoder::tokio_util::Decoder<tokio_util::codec::LengthDelimitedCodec>;
+
+    fn make_state<Algorithm>(
+        decoder: Decoder<Frames, Algorithm>,
+    ) -> crate::State<Decoder<Frames, Algorithm>, bytes::BytesMut> {
+        crate::State::new(decoder, bytes::BytesMut::new())
+    }
+
+    fn frames() -> Frames {
+        crate::decoder::tokio_util::Decoder::new(tokio_util::codec::LengthDelimitedCodec::new())
+    }
+
+    fn length_delimited(frame: &[u8]) -> Vec<u8> {
+        let mut data = u32::try_from(frame.len()).unwrap().to_be_bytes().to_vec();
+        data.extend_from_slice(frame);
+        data
+    }
+
+    fn crc32_trailing(payload: &[u8]) -> Vec<u8> {
+        let mut frame = payload.to_vec();
+        frame.extend(crc32fast::hash(payload).to_be_bytes());
+        length_delimited(&frame)
+    }
+
+    #[test]
+    fn test_algorithms() {
+        let data = b"123456789";
+        assert_eq!(Crc32.checksum(data), 0xcbf4_3926);
+        assert_eq!(Crc32c.checksum(data), 0xe306_9283);
+        assert_eq!(XxHash32 { seed: 0 }.checksum(b""), 0x02cc_5d05);
+        assert_eq!(XxHash64 { seed: 0 }.checksum(b""), 0xef46_db37_51d8_e999);
+    }
+
+    #[test]
+    fn test_crc32_trailing() {
+        let mut dec = make_state(Decoder::new(frames(), Crc32));
+
+        let mut data = crc32_trailing(b"val0");
+        data.extend(crc32_trailing(b"val1"));
+        let (first, second) = data.split_at(14);
+
+        {
+            let mut stream = dec.process_next_chunk(first);
+            assert_eq!(stream.next().unwrap().unwrap(), "val0");
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.process_next_chunk(second);
+            assert_eq!(stream.next().unwrap().unwrap(), "val1");
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_xxhash64_leading_little_endian() {
+        let algorithm = XxHash64 { seed: 42 };
+        let mut dec = make_state(
+            Decoder::new(frames(), algorithm)
+                .with_position(Position::Leading)
+                .with_byte_order(ByteOrder::LittleEndian),
+        );
+
+        let mut frame = xxhash_rust::xxh64::xxh64(b"val0", 42)
+            .to_le_bytes()
+            .to_vec();
+        frame.extend_from_slice(b"val0");
+
+        {
+            let mut stream = dec.process_next_chunk(&length_delimited(&frame));
+            assert_eq!(stream.next().unwrap().unwrap(), "val0");
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    fn corrupted_data() -> Vec<u8> {
+        let mut data = crc32_trailing(b"val0");
+        let mut corrupted = crc32_trailing(b"val1");
+        corrupted[4] = b'X';
+        data.extend(corrupted);
+        data.extend(length_delimited(b"abc"));
+        data.extend(crc32_trailing(b"val3"));
+        data
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut dec = make_state(Decoder::new(frames(), Crc32));
+
+        let results = crate::decoder::test_util::decode_all(&mut dec, &corrupted_data());
+        assert_eq!(results.len(), 4);
+        let mut results = results.into_iter();
+        assert_eq!(results.next().unwrap().unwrap(), "val0");
+        assert!(matches!(
+            results.next().unwrap().unwrap_err(),
+            Error::Mismatch { frame: 1, expected, actual }
+                if expected == u64::from(crc32fast::hash(b"val1"))
+                    && actual == u64::from(crc32fast::hash(b"Xal1"))
+        ));
+        assert!(matches!(
+            results.next().unwrap().unwrap_err(),
+            Error::TooShort { frame: 2, len: 3 }
+        ));
+        assert_eq!(results.next().unwrap().unwrap(), "val3");
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_skip_mismatched() {
+        let mut dec = make_state(Decoder::new(frames(), Crc32).with_skip_mismatched(true));
+
+        let results = crate::decoder::test_util::decode_all(&mut dec, &corrupted_data());
+        assert_eq!(results.len(), 3);
+        let mut results = results.into_iter();
+        assert_eq!(results.next().unwrap().unwrap(), "val0");
+        // The frames that are too short are still reported.
+        assert!(results.next().unwrap().is_err());
+        assert_eq!(results.next().unwrap().unwrap(), "val3");
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_corrupted_checksum() {
+        let mut dec = make_state(Decoder::new(frames(), Crc32c).with_position(Position::Leading));
+
+        let checksum = crc32c::crc32c(b"val0");
+        let mut frame = (checksum ^ 1).to_be_bytes().to_vec();
+        frame.extend_from_slice(b"val0");
+
+        // The corruption of the checksum itself is indistinguishable from
+        // the corruption of the payload.
+        let mut stream = dec.process_next_chunk(&length_delimited(&frame));
+        assert!(matches!(
+            stream.next().unwrap().unwrap_err(),
+            Error::Mismatch { frame: 0, expected, actual }
+                if expected == u64::from(checksum ^ 1) && actual == u64::from(checksum)
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+//! [`ciborium`] integration (i.e. CBOR).
+
+use std::marker::PhantomData;
+
+/// The decoder for [`ciborium`].
+///
+/// Decodes the CBOR sequence, i.e. the stream of concatenated CBOR values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoder<T> {
+    /// The marker for data type to decode.
+    pub data_type: PhantomData<T>,
+}
+
+impl<T> Decoder<T> {
+    /// Create a new [`Decoder`].
+    pub const fn new() -> Self {
+        Self {
+            data_type: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for Decoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, Buffer> crate::Decoder<Buffer> for Decoder<T>
+where
+    T: for<'de> serde::de::Deserialize<'de>,
+    Buffer: crate::Buffer,
+{
+    type Value = T;
+    type Error = ciborium::de::Error<std::io::Error>;
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let buf = input.view();
+        let mut rest = buf;
+        match ciborium::de::from_reader(&mut rest) {
+            Ok(value) => {
+                input.advance(buf.len() - rest.len());
+                Ok(value)
+            }
+            Err(ciborium::de::Error::Io(err))
+                if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                Err(crate::DecodeError::NeedMoreData)
+            }
+            Err(err) => Err(crate::DecodeError::Other(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn encode(field: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(
+            &TestObject {
+                field: field.into(),
+            },
+            &mut data,
+        )
+        .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_chunked() {
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new());
+
+        let mut data = encode("val0");
+        data.extend(encode("val1"));
+
+        let mut values = Vec::new();
+        for chunk in data.chunks(3) {
+            for value in dec.process_next_chunk(chunk) {
+                values.push(value.unwrap().field);
+            }
+        }
+        assert_eq!(values, ["val0", "val1"]);
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_rubbish() {
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new());
+
+        {
+            let mut stream = dec.process_next_chunk(&[0xff]);
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), [0xff]);
+    }
+}
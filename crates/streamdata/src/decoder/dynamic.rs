@@ -0,0 +1,253 @@
+//! Type-erased decoders, for picking the format at runtime.
+
+use std::collections::HashMap;
+
+use crate::decoder::ext::DecoderExt as _;
+
+/// The type-erased error.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The type-erased decoder, yielding the values of the given type.
+///
+/// This is a [`crate::Decoder`] itself, so it can be used with
+/// the [`crate::State`] directly.
+pub type DynDecoder<Buffer, Value> =
+    Box<dyn crate::Decoder<Buffer, Value = Value, Error = BoxError> + Send>;
+
+/// The factory producing new [`DynDecoder`]s.
+pub type Factory<Buffer, Value> = Box<dyn Fn() -> DynDecoder<Buffer, Value> + Send + Sync>;
+
+/// Erase the type of the given decoder.
+///
+/// The values are kept as is; use [`crate::decoder::ext::DecoderExt::map`]
+/// first to convert them to a common type if needed.
+pub fn erase<Decoder, Buffer>(decoder: Decoder) -> DynDecoder<Buffer, Decoder::Value>
+where
+    Decoder: crate::Decoder<Buffer> + Send + 'static,
+    Decoder::Error: Into<BoxError>,
+    Buffer: crate::Buffer + 'static,
+{
+    Box::new(decoder.map_err(Into::into as fn(Decoder::Error) -> BoxError))
+}
+
+/// The registry of the decoder factories keyed by the MIME type, i.e.
+/// the `Content-Type` of the HTTP response.
+///
+/// The lookups ignore the MIME type parameters (i.e. `; charset=utf-8`),
+/// the surrounding whitespace and the case.
+pub struct Registry<Buffer, Value> {
+    /// The factories by the normalized MIME type.
+    factories: HashMap<String, Factory<Buffer, Value>>,
+}
+
+impl<Buffer, Value> Registry<Buffer, Value> {
+    /// Create a new empty [`Registry`].
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Register the decoder factory for the given MIME type, replacing
+    /// the previously registered one, if any.
+    pub fn register<F>(&mut self, mime_type: &str, factory: F) -> &mut Self
+    where
+        F: Fn() -> DynDecoder<Buffer, Value> + Send + Sync + 'static,
+    {
+        self.factories
+            .insert(normalize(mime_type), Box::new(factory));
+        self
+    }
+
+    /// Register the decoder factory for the given MIME type, in the builder
+    /// style.
+    ///
+    /// See [`Self::register`].
+    pub fn with<F>(mut self, mime_type: &str, factory: F) -> Self
+    where
+        F: Fn() -> DynDecoder<Buffer, Value> + Send + Sync + 'static,
+    {
+        self.register(mime_type, factory);
+        self
+    }
+
+    /// Returns `true` if there is a decoder registered for the given content
+    /// type.
+    pub fn supports(&self, content_type: &str) -> bool {
+        self.factories.contains_key(&normalize(content_type))
+    }
+
+    /// Create a new decoder for the given content type.
+    ///
+    /// Returns `None` if no decoder is registered for it.
+    pub fn decoder(&self, content_type: &str) -> Option<DynDecoder<Buffer, Value>> {
+        self.factories
+            .get(&normalize(content_type))
+            .map(|factory| factory())
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<Buffer, Value> Registry<Buffer, Value>
+where
+    Buffer: crate::Buffer + 'static,
+    Value: for<'de> serde::Deserialize<'de> + Send + 'static,
+{
+    /// Create a new [`Registry`] with the decoders for the built-in formats
+    /// registered:
+    ///
+    /// - `application/json`;
+    /// - `application/x-ndjson`, `application/ndjson` and
+    ///   `application/jsonl` (the values are decoded regardless of
+    ///   the newlines, see [`crate::Recovery::SkipToNewline`] to recover
+    ///   from the errors by the lines);
+    /// - `application/msgpack`, `application/x-msgpack` and
+    ///   `application/vnd.msgpack`, with the `rmp-serde` feature;
+    /// - `application/cbor`, with the `ciborium` feature.
+    ///
+    /// See [`Self::with_limited_builtins`] to limit the JSON values.
+    pub fn with_builtins() -> Self {
+        Self::with_builtins_impl(super::json_scanner::Limits::NONE)
+    }
+
+    /// Create a new [`Registry`] with the decoders for the built-in formats
+    /// registered (see [`Self::with_builtins`]), with the size and
+    /// the nesting depth of the JSON values limited (see
+    /// [`crate::decoder::serde_json::Decoder::with_max_size`] and
+    /// [`crate::decoder::serde_json::Decoder::with_max_depth`]).
+    pub fn with_limited_builtins(max_size: Option<usize>, max_depth: Option<usize>) -> Self {
+        Self::with_builtins_impl(super::json_scanner::Limits {
+            max_size,
+            max_depth,
+        })
+    }
+
+    /// Create a new [`Registry`] with the decoders for the built-in formats
+    /// registered, with the given limits on the JSON values.
+    fn with_builtins_impl(limits: super::json_scanner::Limits) -> Self {
+        let json =
+            move || erase(crate::decoder::serde_json::Decoder::<Value>::new().with_limits(limits));
+        let registry = Self::new()
+            .with("application/json", json)
+            .with("application/x-ndjson", json)
+            .with("application/ndjson", json)
+            .with("application/jsonl", json);
+
+        #[cfg(feature = "rmp-serde")]
+        let registry = {
+            let msgpack = || erase(crate::decoder::rmp_serde::Decoder::<Value>::new());
+            registry
+                .with("application/msgpack", msgpack)
+                .with("application/x-msgpack", msgpack)
+                .with("application/vnd.msgpack", msgpack)
+        };
+
+        #[cfg(feature = "ciborium")]
+        let registry = registry.with("application/cbor", || {
+            erase(crate::decoder::ciborium::Decoder::<Value>::new())
+        });
+
+        registry
+    }
+}
+
+impl<Buffer, Value> Default for Registry<Buffer, Value> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Buffer, Value> std::fmt::Debug for Registry<Buffer, Value> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut mime_types: Vec<_> = self.factories.keys().collect();
+        mime_types.sort();
+        f.debug_struct("Registry")
+            .field("mime_types", &mime_types)
+            .finish()
+    }
+}
+
+/// Normalize the MIME type for the lookups.
+fn normalize(content_type: &str) -> String {
+    let essence = content_type
+        .split_once(';')
+        .map_or(content_type, |(essence, _)| essence);
+    essence.trim().to_ascii_lowercase()
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_erase() {
+        let decoders: Vec<DynDecoder<Vec<u8>, serde_json::Value>> = vec![
+            erase(crate::decoder::serde_json::Decoder::new()),
+            erase(
+                crate::decoder::serde_json::Decoder::<String>::new().map(serde_json::Value::from),
+            ),
+        ];
+
+        for decoder in decoders {
+            let mut dec = crate::State::new(decoder, Vec::new());
+            {
+                let mut stream = dec.process_next_chunk(br#""val0""#);
+                assert_eq!(stream.next().unwrap().unwrap(), "val0");
+                assert!(stream.next().is_none());
+            }
+            assert!(dec.finish().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::<Vec<u8>, serde_json::Value>::with_builtins();
+
+        assert!(registry.supports("application/json"));
+        assert!(registry.supports("Application/JSON ; charset=utf-8"));
+        assert!(!registry.supports("text/plain"));
+        assert!(registry.decoder("text/plain").is_none());
+
+        let decoder = registry.decoder("application/x-ndjson").unwrap();
+        let mut dec = crate::State::new(decoder, Vec::new());
+        {
+            let mut stream = dec.process_next_chunk(b"{\"field\": \"val0\"}\n[1, 2]\nqwe\n");
+            assert_eq!(stream.next().unwrap().unwrap()["field"], "val0");
+            assert_eq!(stream.next().unwrap().unwrap(), serde_json::json!([1, 2]));
+            // The errors are type-erased.
+            let err = stream.next().unwrap().unwrap_err();
+            assert!(err.is::<crate::decoder::serde_json::Error>());
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(dec.finish().unwrap_err(), b"\nqwe\n");
+    }
+
+    #[test]
+    fn test_registry_limits() {
+        let registry = Registry::<Vec<u8>, serde_json::Value>::with_limited_builtins(Some(8), None);
+
+        let decoder = registry.decoder("application/json").unwrap();
+        let mut dec = crate::State::new(decoder, Vec::new());
+        let mut stream = dec.process_next_chunk(b"[1, 2]\n[1, 2, 3, 4]");
+        assert_eq!(stream.next().unwrap().unwrap(), serde_json::json!([1, 2]));
+        let err = stream.next().unwrap().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(crate::decoder::serde_json::Error::TooLarge { max_size: 8 })
+        ));
+    }
+
+    #[test]
+    fn test_registry_custom() {
+        let registry = Registry::<Vec<u8>, serde_json::Value>::new().with("text/plain", || {
+            erase(crate::decoder::serde_json::Decoder::<String>::new().map(serde_json::Value::from))
+        });
+
+        assert!(!registry.supports("application/json"));
+        assert!(registry.supports("text/plain"));
+        assert_eq!(
+            format!("{registry:?}"),
+            r#"Registry { mime_types: ["text/plain"] }"#
+        );
+    }
+}
@@ -0,0 +1,479 @@
+//! Decoder combinators.
+//!
+//! The combinators keep the [`crate::DecodeError`] semantics of the wrapped
+//! decoder: [`crate::DecodeError::NeedMoreData`] and
+//! [`crate::DecodeError::SkipData`] are passed through as is, and only
+//! the values and the [`crate::DecodeError::Other`] errors are altered
+//! (or, in case of [`DecoderExt::filter_map`], the values are dropped).
+
+use std::marker::PhantomData;
+
+/// An extension trait for the [`crate::Decoder`]s providing the combinators.
+pub trait DecoderExt<Buffer>: crate::Decoder<Buffer> + Sized
+where
+    Buffer: crate::Buffer,
+{
+    /// Transform the decoded values with the given function.
+    fn map<F, U>(self, f: F) -> Map<Self, F, Buffer>
+    where
+        F: FnMut(Self::Value) -> U,
+    {
+        Map {
+            inner: self,
+            f,
+            buffer: PhantomData,
+        }
+    }
+
+    /// Transform the decoding errors with the given function.
+    fn map_err<F, E>(self, f: F) -> MapErr<Self, F, Buffer>
+    where
+        F: FnMut(Self::Error) -> E,
+    {
+        MapErr {
+            inner: self,
+            f,
+            buffer: PhantomData,
+        }
+    }
+
+    /// Transform the decoded values with the given fallible function.
+    ///
+    /// The error returned by the function is reported as
+    /// [`crate::DecodeError::Other`]; the data of the value is consumed
+    /// regardless.
+    fn and_then<F, U>(self, f: F) -> AndThen<Self, F, Buffer>
+    where
+        F: FnMut(Self::Value) -> Result<U, Self::Error>,
+    {
+        AndThen {
+            inner: self,
+            f,
+            buffer: PhantomData,
+        }
+    }
+
+    /// Transform the decoded values with the given function, dropping
+    /// the values for which it returns [`None`].
+    ///
+    /// The data of the dropped values is consumed, and the decoding proceeds
+    /// to the next value; the dropped values are reported as
+    /// [`crate::DecodeError::SkipData`] of zero bytes, so that their data is
+    /// not attributed to the next value.
+    fn filter_map<F, U>(self, f: F) -> FilterMap<Self, F, Buffer>
+    where
+        F: FnMut(Self::Value) -> Option<U>,
+    {
+        FilterMap {
+            inner: self,
+            f,
+            buffer: PhantomData,
+        }
+    }
+
+    /// Call the given function with a reference to each decoded value.
+    fn inspect<F>(self, f: F) -> Inspect<Self, F, Buffer>
+    where
+        F: FnMut(&Self::Value),
+    {
+        Inspect {
+            inner: self,
+            f,
+            buffer: PhantomData,
+        }
+    }
+}
+
+impl<T, Buffer> DecoderExt<Buffer> for T
+where
+    T: crate::Decoder<Buffer>,
+    Buffer: crate::Buffer,
+{
+}
+
+/// Implement the common traits for a combinator.
+///
+/// The function is omitted from the [`std::fmt::Debug`] output, and
+/// the buffer type is not required to implement anything.
+macro_rules! impl_common {
+    ($name:ident) => {
+        impl<T, F, Buffer> std::fmt::Debug for $name<T, F, Buffer>
+        where
+            T: std::fmt::Debug,
+        {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("inner", &self.inner)
+                    .finish_non_exhaustive()
+            }
+        }
+
+        impl<T, F, Buffer> Clone for $name<T, F, Buffer>
+        where
+            T: Clone,
+            F: Clone,
+        {
+            fn clone(&self) -> Self {
+                Self {
+                    inner: self.inner.clone(),
+                    f: self.f.clone(),
+                    buffer: PhantomData,
+                }
+            }
+        }
+    };
+}
+
+/// The decoder for [`DecoderExt::map`].
+pub struct Map<T, F, Buffer> {
+    /// The inner decoder.
+    pub inner: T,
+    /// The function to apply.
+    f: F,
+    /// The buffer type the decoder is used with.
+    buffer: PhantomData<fn(&mut Buffer)>,
+}
+
+impl_common!(Map);
+
+impl<T, F, U, Buffer> crate::Decoder<Buffer> for Map<T, F, Buffer>
+where
+    T: crate::Decoder<Buffer>,
+    F: FnMut(T::Value) -> U,
+    Buffer: crate::Buffer,
+{
+    type Value = U;
+    type Error = T::Error;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        self.inner.decode(input).map(&mut self.f)
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        self.inner.decode_eof(input).map(&mut self.f)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// The decoder for [`DecoderExt::map_err`].
+pub struct MapErr<T, F, Buffer> {
+    /// The inner decoder.
+    pub inner: T,
+    /// The function to apply.
+    f: F,
+    /// The buffer type the decoder is used with.
+    buffer: PhantomData<fn(&mut Buffer)>,
+}
+
+impl_common!(MapErr);
+
+impl<T, F, Buffer> MapErr<T, F, Buffer> {
+    /// Apply the function to the error, if any.
+    fn map<V, E, U>(
+        &mut self,
+        result: Result<V, crate::DecodeError<E>>,
+    ) -> Result<V, crate::DecodeError<U>>
+    where
+        F: FnMut(E) -> U,
+    {
+        result.map_err(|err| match err {
+            crate::DecodeError::NeedMoreData => crate::DecodeError::NeedMoreData,
+            crate::DecodeError::SkipData(bytes) => crate::DecodeError::SkipData(bytes),
+            crate::DecodeError::Other(err) => crate::DecodeError::Other((self.f)(err)),
+            crate::DecodeError::Rejected(err) => crate::DecodeError::Rejected((self.f)(err)),
+        })
+    }
+}
+
+impl<T, F, E, Buffer> crate::Decoder<Buffer> for MapErr<T, F, Buffer>
+where
+    T: crate::Decoder<Buffer>,
+    F: FnMut(T::Error) -> E,
+    Buffer: crate::Buffer,
+{
+    type Value = T::Value;
+    type Error = E;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let result = self.inner.decode(input);
+        self.map(result)
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let result = self.inner.decode_eof(input);
+        self.map(result)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// The decoder for [`DecoderExt::and_then`].
+pub struct AndThen<T, F, Buffer> {
+    /// The inner decoder.
+    pub inner: T,
+    /// The function to apply.
+    f: F,
+    /// The buffer type the decoder is used with.
+    buffer: PhantomData<fn(&mut Buffer)>,
+}
+
+impl_common!(AndThen);
+
+impl<T, F, U, Buffer> crate::Decoder<Buffer> for AndThen<T, F, Buffer>
+where
+    T: crate::Decoder<Buffer>,
+    F: FnMut(T::Value) -> Result<U, T::Error>,
+    Buffer: crate::Buffer,
+{
+    type Value = U;
+    type Error = T::Error;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let value = self.inner.decode(input)?;
+        (self.f)(value).map_err(crate::DecodeError::Other)
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let value = self.inner.decode_eof(input)?;
+        (self.f)(value).map_err(crate::DecodeError::Other)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// The decoder for [`DecoderExt::filter_map`].
+pub struct FilterMap<T, F, Buffer> {
+    /// The inner decoder.
+    pub inner: T,
+    /// The function to apply.
+    f: F,
+    /// The buffer type the decoder is used with.
+    buffer: PhantomData<fn(&mut Buffer)>,
+}
+
+impl_common!(FilterMap);
+
+impl<T, F, U, Buffer> crate::Decoder<Buffer> for FilterMap<T, F, Buffer>
+where
+    T: crate::Decoder<Buffer>,
+    F: FnMut(T::Value) -> Option<U>,
+    Buffer: crate::Buffer,
+{
+    type Value = U;
+    type Error = T::Error;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let value = self.inner.decode(input)?;
+        (self.f)(value).ok_or(crate::DecodeError::SkipData(0))
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let value = self.inner.decode_eof(input)?;
+        (self.f)(value).ok_or(crate::DecodeError::SkipData(0))
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// The decoder for [`DecoderExt::inspect`].
+pub struct Inspect<T, F, Buffer> {
+    /// The inner decoder.
+    pub inner: T,
+    /// The function to call.
+    f: F,
+    /// The buffer type the decoder is used with.
+    buffer: PhantomData<fn(&mut Buffer)>,
+}
+
+impl_common!(Inspect);
+
+impl<T, F, Buffer> crate::Decoder<Buffer> for Inspect<T, F, Buffer>
+where
+    T: crate::Decoder<Buffer>,
+    F: FnMut(&T::Value),
+    Buffer: crate::Buffer,
+{
+    type Value = T::Value;
+    type Error = T::Error;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let value = self.inner.decode(input)?;
+        (self.f)(&value);
+        Ok(value)
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let value = self.inner.decode_eof(input)?;
+        (self.f)(&value);
+        Ok(value)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn make_decoder() -> crate::decoder::serde_json::Decoder<TO> {
+        crate::decoder::serde_json::Decoder::new()
+    }
+
+    #[test]
+    fn test_map() {
+        let mut dec = crate::State::new(make_decoder().map(|value| value.field), Vec::new());
+
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"}{"fie"#);
+            assert_eq!(stream.next().unwrap().unwrap(), "val0");
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.process_next_chunk(br#"ld": "val1"}"#);
+            assert_eq!(stream.next().unwrap().unwrap(), "val1");
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_map_err() {
+        let mut dec = crate::State::new(make_decoder().map_err(|err| err.to_string()), Vec::new());
+
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"}qwe"#);
+            assert_eq!(stream.next().unwrap().unwrap().field, "val0");
+            assert_eq!(
+                stream.next().unwrap().unwrap_err(),
+                "expected value at line 1 column 1"
+            );
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), b"qwe");
+    }
+
+    #[test]
+    fn test_and_then() {
+        let mut dec = crate::State::new(
+            make_decoder().and_then(|value| {
+                value.field.parse::<u32>().map_err(|err| {
+                    crate::decoder::serde_json::Error::Json(serde::de::Error::custom(err))
+                })
+            }),
+            Vec::new(),
+        );
+
+        {
+            let mut stream =
+                dec.process_next_chunk(br#"{"field": "1"}{"field": "val"}{"field": "2"}"#);
+            assert_eq!(stream.next().unwrap().unwrap(), 1);
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+        }
+
+        // The data of the value that failed to convert is consumed.
+        {
+            let mut stream = dec.process_next_chunk(b"");
+            assert_eq!(stream.next().unwrap().unwrap(), 2);
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_filter_map() {
+        let mut dec = crate::State::new(
+            make_decoder().filter_map(|value| value.field.strip_prefix("keep:").map(str::to_owned)),
+            Vec::new(),
+        );
+
+        {
+            let stream = dec.process_next_chunk(
+                br#"{"field": "drop:0"}{"field": "keep:1"}{"field": "drop:2"}{"field": "drop:3"}{"field": "keep:4"}{"field": "drop:5"}"#,
+            );
+            let values = stream
+                .with_offsets()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let values = values
+                .into_iter()
+                .map(|decoded| (decoded.value, decoded.start, decoded.end))
+                .collect::<Vec<_>>();
+            assert_eq!(values, [("1".to_owned(), 19, 38), ("4".to_owned(), 76, 95)]);
+        }
+
+        // The trailing dropped value is consumed as well.
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_inspect() {
+        let mut seen = Vec::new();
+
+        {
+            let mut dec = crate::State::new(
+                make_decoder().inspect(|value| seen.push(value.field.clone())),
+                Vec::new(),
+            );
+            let values = dec
+                .process_next_chunk(br#"{"field": "val0"}{"field": "val1"}"#)
+                .try_collect::<Vec<_>>()
+                .unwrap();
+            assert_eq!(values.len(), 2);
+        }
+
+        assert_eq!(seen, ["val0", "val1"]);
+    }
+}
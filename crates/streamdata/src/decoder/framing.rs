@@ -0,0 +1,61 @@
+//! The handling of the frames shared by the decoders that process the frames
+//! produced by another decoder (see [`super::layered`] and
+//! [`super::checksum`]).
+
+/// The counter of the frames produced by the inner decoder, used to tell
+/// which frame an error is about.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Counter {
+    /// The index of the next frame.
+    next: usize,
+}
+
+impl Counter {
+    /// Create a new [`Counter`] at the start of the stream.
+    pub(super) const fn new() -> Self {
+        Self { next: 0 }
+    }
+
+    /// Count the next frame, returning its index.
+    pub(super) fn next(&mut self) -> usize {
+        let index = self.next;
+        self.next = self.next.wrapping_add(1);
+        index
+    }
+}
+
+/// Reject the frame, either by reporting the error or, if `skip` is set, by
+/// skipping the frame.
+///
+/// The frame is consumed by the inner decoder before it can be processed, so
+/// there is nothing left to skip in the buffer, and the decoding naturally
+/// proceeds with the next frame either way; the error is thus reported as
+/// [`crate::DecodeError::Rejected`], so that the [`crate::State`] does not
+/// drop any data of the next frame to recover.
+pub(super) fn reject<T, E>(
+    error: E,
+    skip: bool,
+    frame: usize,
+    reason: &str,
+) -> Result<T, crate::DecodeError<E>> {
+    if skip {
+        tracing::warn!(frame, "skipping the frame: {reason}");
+        return Err(crate::DecodeError::SkipData(0));
+    }
+    Err(crate::DecodeError::Rejected(error))
+}
+
+/// Wrap the error of the inner decoder, passing
+/// [`crate::DecodeError::NeedMoreData`] and [`crate::DecodeError::SkipData`]
+/// through as is.
+pub(super) fn inner_error<InnerError, Error>(
+    err: crate::DecodeError<InnerError>,
+    wrap: impl FnOnce(InnerError) -> Error,
+) -> crate::DecodeError<Error> {
+    match err {
+        crate::DecodeError::NeedMoreData => crate::DecodeError::NeedMoreData,
+        crate::DecodeError::SkipData(bytes) => crate::DecodeError::SkipData(bytes),
+        crate::DecodeError::Other(err) => crate::DecodeError::Other(wrap(err)),
+        crate::DecodeError::Rejected(err) => crate::DecodeError::Rejected(wrap(err)),
+    }
+}
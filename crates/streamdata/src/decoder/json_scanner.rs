@@ -0,0 +1,223 @@
+//! The resumable scanner of the JSON value boundaries, shared by the JSON
+//! decoders.
+
+/// The limits on a single JSON value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Limits {
+    /// The maximum size of the value in bytes, not counting the whitespace
+    /// preceding it.
+    pub(super) max_size: Option<usize>,
+    /// The maximum nesting depth of the objects and arrays in the value.
+    pub(super) max_depth: Option<usize>,
+}
+
+impl Limits {
+    /// No limits.
+    pub(super) const NONE: Self = Self {
+        max_size: None,
+        max_depth: None,
+    };
+}
+
+/// The limit exceeded by the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Exceeded {
+    /// The value is larger than the given size.
+    Size(usize),
+    /// The value is nested deeper than the given depth.
+    Depth(usize),
+}
+
+/// The resumable scanner looking for the end of the first JSON value in
+/// the buffer.
+///
+/// It only tracks the structure of the data (the nesting, the strings and
+/// the escapes), leaving the actual validation to the parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Scanner {
+    /// The amount of bytes scanned so far.
+    scanned: usize,
+    /// The offset of the first byte of the value, past the whitespace
+    /// preceding it.
+    start: usize,
+    /// The amount of bytes at the last syntax check of the incomplete value.
+    pub(super) checked: usize,
+    /// The nesting depth of the objects and arrays.
+    depth: usize,
+    /// The state at the current position.
+    state: State,
+}
+
+/// The state of the [`Scanner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the value, skipping the whitespace.
+    Start,
+    /// Inside a top-level number.
+    Number,
+    /// Inside an object or an array, outside of the strings.
+    Nested,
+    /// Inside a string.
+    String,
+    /// Right after the backslash inside a string.
+    Escape,
+}
+
+impl Scanner {
+    /// Create a new [`Scanner`] at the start of the buffer.
+    pub(super) const fn new() -> Self {
+        Self {
+            scanned: 0,
+            start: 0,
+            checked: 0,
+            depth: 0,
+            state: State::Start,
+        }
+    }
+
+    /// Returns `true` if the scanner is inside a top-level number, i.e.
+    /// the value is terminated by the end of input.
+    #[cfg(feature = "serde_json")]
+    pub(super) fn in_number(&self) -> bool {
+        self.state == State::Number
+    }
+
+    /// Continue scanning the buffer, returning the offset of the end of
+    /// the first value once it is known.
+    ///
+    /// The literals and the invalid data are not scanned, but rather
+    /// handed to the parser as is right away.
+    ///
+    /// Once the end of the value is found, scanning the same (or extended)
+    /// buffer again returns the same end without rescanning the value.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub(super) fn scan(&mut self, buf: &[u8], limits: Limits) -> Result<Option<usize>, Exceeded> {
+        for (index, &byte) in buf.iter().enumerate().skip(self.scanned) {
+            if self.state == State::Start {
+                self.start = index;
+            }
+            // The byte ending the value is scanned again on the next call.
+            self.scanned = index;
+            self.state = match (self.state, byte) {
+                (State::Start, b' ' | b'\t' | b'\n' | b'\r') => State::Start,
+                (State::Start, b'-' | b'0'..=b'9')
+                | (State::Number, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') => State::Number,
+                (State::Start | State::Nested, b'{' | b'[') => {
+                    self.depth += 1;
+                    if let Some(max_depth) = limits.max_depth.filter(|&max| self.depth > max) {
+                        return Err(Exceeded::Depth(max_depth));
+                    }
+                    State::Nested
+                }
+                (State::Start | State::Nested, b'"') => State::String,
+                (State::Start, _) => return Ok(Some(buf.len())),
+                (State::Number, _) => return self.check_size(index, limits).map(Some),
+                (State::Nested, b'}' | b']') if self.depth == 1 => {
+                    return self.check_size(index + 1, limits).map(Some)
+                }
+                (State::Nested, b'}' | b']') => {
+                    self.depth -= 1;
+                    State::Nested
+                }
+                (State::String, b'\\') => State::Escape,
+                (State::String, b'"') if self.depth == 0 => {
+                    return self.check_size(index + 1, limits).map(Some)
+                }
+                (State::Nested, _) | (State::String, b'"') => State::Nested,
+                (State::String | State::Escape, _) => State::String,
+            };
+        }
+        self.scanned = buf.len();
+        if self.state == State::Start {
+            // Only the whitespace so far.
+            return Ok(None);
+        }
+        // All of the data belongs to the incomplete value.
+        self.check_size(buf.len(), limits).map(|_| None)
+    }
+
+    /// Check the size of the value ending at the given offset against
+    /// the limits, returning the offset.
+    fn check_size(&self, end: usize, limits: Limits) -> Result<usize, Exceeded> {
+        match limits.max_size {
+            Some(max_size) if end.saturating_sub(self.start) > max_size => {
+                Err(Exceeded::Size(max_size))
+            }
+            _ => Ok(end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_chunked(data: &[u8], limits: Limits) -> Result<Option<usize>, Exceeded> {
+        let mut scanner = Scanner::new();
+        for len in 1..data.len() {
+            if let Some(end) = scanner.scan(&data[..len], limits)? {
+                return Ok(Some(end));
+            }
+        }
+        scanner.scan(data, limits)
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let cases: &[(&[u8], Option<usize>)] = &[
+            (b"", None),
+            (b"  ", None),
+            (b" {}", Some(3)),
+            (br#"{"a": [1, {"b": "}"}]} {}"#, Some(22)),
+            (br#"["\"]", "\\"] 1"#, Some(13)),
+            (br#""a\"b" 1"#, Some(6)),
+            (b" 12 ", Some(3)),
+            (b" 12", None),
+            (b"[[]", None),
+        ];
+        for &(data, expected) in cases {
+            assert_eq!(scan_chunked(data, Limits::NONE), Ok(expected), "{data:?}");
+        }
+    }
+
+    #[test]
+    fn test_scan_again() {
+        let mut scanner = Scanner::new();
+        assert_eq!(scanner.scan(br#"{"a": [1"#, Limits::NONE), Ok(None));
+        assert_eq!(scanner.scan(br#"{"a": [1]}"#, Limits::NONE), Ok(Some(10)));
+        assert_eq!(
+            scanner.scan(br#"{"a": [1]} {}"#, Limits::NONE),
+            Ok(Some(10))
+        );
+
+        let mut scanner = Scanner::new();
+        assert_eq!(scanner.scan(b"12", Limits::NONE), Ok(None));
+        assert_eq!(scanner.scan(b"12,", Limits::NONE), Ok(Some(2)));
+        assert_eq!(scanner.scan(b"12, ", Limits::NONE), Ok(Some(2)));
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            max_size: Some(8),
+            max_depth: Some(2),
+        };
+        assert_eq!(scan_chunked(b"[[1]]", limits), Ok(Some(5)));
+        assert_eq!(scan_chunked(br#"{"a": 1}"#, limits), Ok(Some(8)));
+        assert_eq!(scan_chunked(b"[[[", limits), Err(Exceeded::Depth(2)));
+        assert_eq!(
+            scan_chunked(br#"{"a": 10}"#, limits),
+            Err(Exceeded::Size(8))
+        );
+        assert_eq!(
+            scan_chunked(b"[\"123456789", limits),
+            Err(Exceeded::Size(8))
+        );
+        // The whitespace preceding the value does not count.
+        assert_eq!(scan_chunked(b"         ", limits), Ok(None));
+        assert_eq!(
+            scan_chunked(b"\n\n\n\n\n\n\n\n\n[[1,2]]", limits),
+            Ok(Some(16))
+        );
+    }
+}
@@ -2,13 +2,25 @@
 
 use std::marker::PhantomData;
 
+use super::json_scanner::{Exceeded, Limits, Scanner};
+
 /// The decoder for [`k8s_openapi::Response`].
+///
+/// The size and the nesting depth of the JSON responses can be limited (see
+/// [`Decoder::with_max_size`] and [`Decoder::with_max_depth`]), in which
+/// case the error is reported as soon as the limit is exceeded, without
+/// waiting for the rest of the response.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Decoder<T> {
     /// The marker for data type to decode.
     pub data_type: PhantomData<T>,
     /// The status code to present to [`k8s_openapi::Response::try_from_parts`].
     pub status_code: k8s_openapi::http::StatusCode,
+    /// The limits on the responses.
+    limits: Limits,
+    /// The scanner of the response being buffered, used to enforce
+    /// the limits.
+    scanner: Scanner,
 }
 
 impl<T> Decoder<T> {
@@ -17,6 +29,8 @@ impl<T> Decoder<T> {
         Self {
             data_type: PhantomData,
             status_code,
+            limits: Limits::NONE,
+            scanner: Scanner::new(),
         }
     }
 
@@ -25,6 +39,39 @@ impl<T> Decoder<T> {
     pub const fn new() -> Self {
         Self::with_status_code(k8s_openapi::http::StatusCode::OK)
     }
+
+    /// Limit the size of a single response in bytes, not counting
+    /// the whitespace preceding it.
+    pub const fn with_max_size(mut self, max_size: usize) -> Self {
+        self.limits.max_size = Some(max_size);
+        self
+    }
+
+    /// Limit the nesting depth of the objects and arrays in a single
+    /// response.
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.limits.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Check the response being buffered against the limits, if any.
+    fn check_limits(&mut self, buf: &[u8]) -> Result<(), Error> {
+        if self.limits == Limits::NONE {
+            return Ok(());
+        }
+        match self.scanner.scan(buf, self.limits) {
+            Ok(None) => Ok(()),
+            Ok(Some(_)) => {
+                // The response is complete, start over with the next one.
+                self.scanner = Scanner::new();
+                Ok(())
+            }
+            Err(exceeded) => {
+                self.scanner = Scanner::new();
+                Err(exceeded.into())
+            }
+        }
+    }
 }
 
 impl<T> Default for Decoder<T> {
@@ -49,8 +96,10 @@ where
         let buf = input.view();
         // Allow skipping over newlines.
         if matches!(buf.first(), Some(b'\n')) {
+            self.scanner = Scanner::new();
             return Err(crate::DecodeError::SkipData(1));
         }
+        self.check_limits(buf).map_err(crate::DecodeError::Other)?;
         match T::try_from_parts(self.status_code, buf) {
             Ok((value, mut consumed_bytes)) => {
                 // Consume one extra character if the newline follows
@@ -70,10 +119,15 @@ where
             }
         }
     }
+
+    fn reset(&mut self) {
+        self.scanner = Scanner::new();
+    }
 }
 
 /// The error that can occur when decoding the [`k8s_openapi::Response`].
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     /// An error while deserializing the HTTP response as a JSON value,
     /// indicating the response is malformed.
@@ -83,6 +137,29 @@ pub enum Error {
     /// An error while deserializing the HTTP response as a string, indicating that the response data is not UTF-8.
     #[error("utf8: {0}")]
     Utf8(#[source] std::str::Utf8Error),
+
+    /// The response is larger than the limit.
+    #[error("response exceeds the size limit of {max_size} bytes")]
+    TooLarge {
+        /// The maximum size of the response in bytes.
+        max_size: usize,
+    },
+
+    /// The response is nested deeper than the limit.
+    #[error("response exceeds the nesting depth limit of {max_depth}")]
+    TooDeep {
+        /// The maximum nesting depth of the response.
+        max_depth: usize,
+    },
+}
+
+impl From<Exceeded> for Error {
+    fn from(exceeded: Exceeded) -> Self {
+        match exceeded {
+            Exceeded::Size(max_size) => Self::TooLarge { max_size },
+            Exceeded::Depth(max_depth) => Self::TooDeep { max_depth },
+        }
+    }
 }
 
 impl From<Error> for k8s_openapi::ResponseError {
@@ -90,6 +167,9 @@ impl From<Error> for k8s_openapi::ResponseError {
         match value {
             Error::Json(val) => Self::Json(val),
             Error::Utf8(val) => Self::Utf8(val),
+            err @ (Error::TooLarge { .. } | Error::TooDeep { .. }) => {
+                Self::Json(k8s_openapi::serde::de::Error::custom(err))
+            }
         }
     }
 }
@@ -132,10 +212,7 @@ mod tests {
     }
 
     fn make_state<T>() -> crate::State<Decoder<T>, Vec<u8>> {
-        crate::State {
-            buffer: Vec::new(),
-            decoder: Decoder::new(),
-        }
+        crate::State::new(Decoder::new(), Vec::new())
     }
 
     #[test]
@@ -169,6 +246,45 @@ mod tests {
         assert_eq!(dec.finish().unwrap_err(), b"qwerty");
     }
 
+    #[test]
+    fn test_limits() {
+        let mut dec = crate::State::new(Decoder::<TO>::new().with_max_size(100), Vec::new())
+            .with_recovery(crate::Recovery::SkipToNewline);
+
+        {
+            let stream = dec.process_next_chunk(
+                br#"{"type": "ADDED", "object": {"kind": "Pod", "apiVersion": "v1", "metadata": {"uid": "uid0"}}}
+{"type": "ADDED", "object": {"kind": "Pod", "apiVersion": "v1", "metadata": {"uid": "uid1", "annotations": {"#,
+            );
+            let results: Vec<_> = stream.collect();
+            assert_eq!(results.len(), 2);
+            assert_test_object(results.into_iter().next(), "uid0");
+        }
+
+        {
+            // The rest of the response that is too large is skipped.
+            let mut stream = dec.process_next_chunk(
+                br#""a": "b"}}}}
+{"type": "ADDED", "object": {"kind": "Pod", "apiVersion": "v1", "metadata": {"uid": "uid2"}}}"#,
+            );
+            assert_test_object(stream.next(), "uid2");
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+
+        let mut dec = crate::State::new(Decoder::<TO>::new().with_max_depth(2), Vec::new());
+        {
+            let mut stream =
+                dec.process_next_chunk(br#"{"type": "ADDED", "object": {"metadata": {"#);
+            assert!(matches!(
+                stream.next(),
+                Some(Err(Error::TooDeep { max_depth: 2 }))
+            ));
+            assert!(stream.next().is_none());
+        }
+    }
+
     #[test]
     fn test_one() {
         let mut dec = make_state::<TO>();
@@ -0,0 +1,362 @@
+//! Layered decoding, where a framing decoder feeds a payload decoder.
+
+/// The decoder that splits the data into frames with one decoder, and then
+/// decodes the payload of each frame with another decoder.
+///
+/// The frames are decoded into values that act as a [`crate::Buffer`]
+/// (i.e. [`Vec<u8>`] or [`bytes::BytesMut`]), and each frame must contain
+/// exactly one payload value.
+/// Use [`crate::decoder::ext::DecoderExt::map`] to convert the frames
+/// into a buffer if the frame decoder yields something else.
+///
+/// By default, a payload that fails to decode, is incomplete, or is followed
+/// by some data within its frame is reported as an [`Error`], and
+/// the decoding then carries on with the payload of the next frame.
+/// The frame is consumed along with the error (see
+/// [`crate::DecodeError::Rejected`]), so no data of the next frame is dropped
+/// whatever the [`crate::Recovery`] policy.
+/// See [`Decoder::with_skip_invalid_payloads`] to skip such frames instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoder<Frame, Payload> {
+    /// The frame decoder.
+    pub frame: Frame,
+    /// The payload decoder.
+    pub payload: Payload,
+    /// Whether to skip the frames with invalid payloads instead of reporting
+    /// an error.
+    pub skip_invalid_payloads: bool,
+    /// The counter of the frames.
+    frames: super::framing::Counter,
+}
+
+impl<Frame, Payload> Decoder<Frame, Payload> {
+    /// Create a new [`Decoder`] with the given frame and payload decoders.
+    pub const fn new(frame: Frame, payload: Payload) -> Self {
+        Self {
+            frame,
+            payload,
+            skip_invalid_payloads: false,
+            frames: super::framing::Counter::new(),
+        }
+    }
+
+    /// Skip the frames with invalid payloads (via
+    /// [`crate::DecodeError::SkipData`]) instead of reporting an error.
+    pub const fn with_skip_invalid_payloads(mut self, skip_invalid_payloads: bool) -> Self {
+        self.skip_invalid_payloads = skip_invalid_payloads;
+        self
+    }
+
+    /// Decode the payload of the given frame.
+    fn decode_payload<FrameBuffer, FrameError>(
+        &mut self,
+        mut frame: FrameBuffer,
+    ) -> Result<Payload::Value, crate::DecodeError<Error<FrameError, Payload::Error>>>
+    where
+        FrameBuffer: crate::Buffer,
+        Payload: crate::Decoder<FrameBuffer>,
+    {
+        let index = self.frames.next();
+        // Each frame is decoded from scratch, whatever the previous one left.
+        self.payload.reset();
+
+        let error = loop {
+            match self.payload.decode_eof(&mut frame) {
+                Ok(_) if !frame.is_empty() => {
+                    break Error::TrailingData {
+                        frame: index,
+                        bytes: frame.len(),
+                    }
+                }
+                Ok(value) => return Ok(value),
+                Err(crate::DecodeError::NeedMoreData) => {
+                    break Error::IncompletePayload { frame: index }
+                }
+                Err(crate::DecodeError::SkipData(bytes_to_skip)) => {
+                    frame.advance(bytes_to_skip);
+                    continue;
+                }
+                Err(crate::DecodeError::Other(error) | crate::DecodeError::Rejected(error)) => {
+                    break Error::Payload {
+                        error,
+                        frame: index,
+                    }
+                }
+            }
+        };
+
+        super::framing::reject(error, self.skip_invalid_payloads, index, "invalid payload")
+    }
+}
+
+impl<Frame, Payload, Buffer> crate::Decoder<Buffer> for Decoder<Frame, Payload>
+where
+    Buffer: crate::Buffer,
+    Frame: crate::Decoder<Buffer>,
+    Frame::Value: crate::Buffer,
+    Payload: crate::Decoder<Frame::Value>,
+{
+    type Value = Payload::Value;
+    type Error = Error<Frame::Error, Payload::Error>;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let frame = self
+            .frame
+            .decode(input)
+            .map_err(|err| super::framing::inner_error(err, Error::Frame))?;
+        self.decode_payload(frame)
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let frame = self
+            .frame
+            .decode_eof(input)
+            .map_err(|err| super::framing::inner_error(err, Error::Frame))?;
+        self.decode_payload(frame)
+    }
+
+    fn reset(&mut self) {
+        self.frame.reset();
+        self.payload.reset();
+    }
+}
+
+/// An error that can occur while decoding the layered data.
+#[derive(Debug)]
+pub enum Error<FrameError, PayloadError> {
+    /// Unable to decode the frame.
+    Frame(FrameError),
+    /// Unable to decode the payload of the frame.
+    Payload {
+        /// The payload decoding error.
+        error: PayloadError,
+        /// The index of the frame in the stream, starting from zero.
+        frame: usize,
+    },
+    /// The frame ended before the payload was complete.
+    IncompletePayload {
+        /// The index of the frame in the stream, starting from zero.
+        frame: usize,
+    },
+    /// The frame has some data left after the payload.
+    TrailingData {
+        /// The index of the frame in the stream, starting from zero.
+        frame: usize,
+        /// The amount of bytes left.
+        bytes: usize,
+    },
+}
+
+impl<FrameError, PayloadError> std::fmt::Display for Error<FrameError, PayloadError>
+where
+    FrameError: std::fmt::Display,
+    PayloadError: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Frame(err) => write!(f, "frame: {err}"),
+            Self::Payload { error, frame } => write!(f, "payload of frame {frame}: {error}"),
+            Self::IncompletePayload { frame } => {
+                write!(f, "payload of frame {frame}: incomplete")
+            }
+            Self::TrailingData { frame, bytes } => {
+                write!(
+                    f,
+                    "payload of frame {frame}: {bytes} bytes of trailing data"
+                )
+            }
+        }
+    }
+}
+
+impl<FrameError, PayloadError> std::error::Error for Error<FrameError, PayloadError>
+where
+    FrameError: std::error::Error + 'static,
+    PayloadError: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Frame(err) => Some(err),
+            Self::Payload { error, .. } => Some(error),
+            Self::IncompletePayload { .. } | Self::TrailingData { .. } => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde_json", feature = "tokio-util"))]
+mod tests {
+    use super::*;
+    use crate::decoder::ext::DecoderExt as _;
+
+    #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn length_delimited(payload: &[u8]) -> Vec<u8> {
+        let mut frame = u32::try_from(payload.len()).unwrap().to_be_bytes().to_vec();
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_length_delimited_json() {
+        let mut dec = crate::State::new(
+            Decoder::new(
+                crate::decoder::tokio_util::Decoder::new(
+                    tokio_util::codec::LengthDelimitedCodec::new(),
+                ),
+                crate::decoder::serde_json::Decoder::<TO>::new(),
+            ),
+            bytes::BytesMut::new(),
+        );
+
+        let mut data = length_delimited(br#"{"field": "val0"}"#);
+        data.extend(length_delimited(br#"{"field": "val1"}"#));
+        let (first, second) = data.split_at(25);
+
+        {
+            let mut stream = dec.process_next_chunk(first);
+            assert_eq!(stream.next().unwrap().unwrap().field, "val0");
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.process_next_chunk(second);
+            assert_eq!(stream.next().unwrap().unwrap().field, "val1");
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    type Lines = crate::decoder::ext::Map<
+        crate::decoder::tokio_util::Decoder<tokio_util::codec::LinesCodec>,
+        fn(String) -> Vec<u8>,
+        Vec<u8>,
+    >;
+
+    fn make_lines_state(
+        skip_invalid_payloads: bool,
+    ) -> crate::State<Decoder<Lines, crate::decoder::serde_json::Decoder<TO>>, Vec<u8>> {
+        crate::State::new(
+            Decoder::new(
+                crate::decoder::tokio_util::Decoder::new(tokio_util::codec::LinesCodec::new())
+                    .map(String::into_bytes as fn(String) -> Vec<u8>),
+                crate::decoder::serde_json::Decoder::<TO>::new(),
+            )
+            .with_skip_invalid_payloads(skip_invalid_payloads),
+            Vec::new(),
+        )
+    }
+
+    const LINES: &str = concat!(
+        r#"{"field": "val0"}"#,
+        "\n",
+        r#"{"field": "#,
+        "\n",
+        r#"{"field": "val2"} {}"#,
+        "\n",
+        "qwerty\n",
+        r#"{"field": "val4"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn test_payload_errors() {
+        let mut dec = make_lines_state(false);
+
+        let results = crate::decoder::test_util::decode_all(&mut dec, LINES.as_bytes());
+        assert_eq!(results.len(), 5);
+        let mut results = results.into_iter();
+        assert_eq!(results.next().unwrap().unwrap().field, "val0");
+        assert!(matches!(
+            results.next().unwrap().unwrap_err(),
+            Error::IncompletePayload { frame: 1 }
+        ));
+        assert!(matches!(
+            results.next().unwrap().unwrap_err(),
+            Error::TrailingData { frame: 2, bytes: 3 }
+        ));
+        assert!(matches!(
+            results.next().unwrap().unwrap_err(),
+            Error::Payload { frame: 3, .. }
+        ));
+        assert_eq!(results.next().unwrap().unwrap().field, "val4");
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_recovery() {
+        let mut dec = make_lines_state(false).with_recovery(crate::Recovery::SkipToNewline);
+
+        // The frames with invalid payloads are consumed already, so none of
+        // the following frames are dropped to recover.
+        let results: Vec<_> = dec
+            .process_next_chunk(LINES.as_bytes())
+            .map(|result| result.map(|value| value.field).map_err(|_| ()))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                Ok("val0".to_owned()),
+                Err(()),
+                Err(()),
+                Err(()),
+                Ok("val4".to_owned())
+            ]
+        );
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_payload_state_is_reset() {
+        let mut dec = crate::State::new(
+            Decoder::new(
+                crate::decoder::tokio_util::Decoder::new(tokio_util::codec::LinesCodec::new())
+                    .map(String::into_bytes as fn(String) -> Vec<u8>),
+                crate::decoder::serde_json::Decoder::<serde_json::Value>::new(),
+            )
+            .with_skip_invalid_payloads(true),
+            Vec::new(),
+        );
+
+        // The unterminated string must not leak into the next frame.
+        let mut stream = dec.process_next_chunk(b"\"abc\n{\"a\":1}\n");
+        assert_eq!(stream.next().unwrap().unwrap(), serde_json::json!({"a": 1}));
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_skip_invalid_payloads() {
+        let mut dec = make_lines_state(true);
+
+        {
+            let stream = dec.process_next_chunk(LINES.as_bytes());
+            let values: Vec<_> = stream.try_collect().unwrap();
+            assert_eq!(
+                values,
+                [
+                    TestObject {
+                        field: "val0".into()
+                    },
+                    TestObject {
+                        field: "val4".into()
+                    }
+                ]
+            );
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+}
@@ -0,0 +1,221 @@
+//! Decoder wrapper that returns the raw encoded bytes alongside each
+//! decoded value.
+
+/// The value decoded by the inner decoder.
+type InnerValue<T, Buffer> = <T as crate::Decoder<Lookahead<Buffer>>>::Value;
+
+/// The error of the inner decoder.
+type InnerError<T, Buffer> = <T as crate::Decoder<Lookahead<Buffer>>>::Error;
+
+/// The decode error of the inner decoder.
+type InnerDecodeError<T, Buffer> = crate::DecodeError<InnerError<T, Buffer>>;
+
+/// The result of the inner decoder.
+type InnerResult<T, Buffer> = Result<InnerValue<T, Buffer>, InnerDecodeError<T, Buffer>>;
+
+/// The buffer that can hand out its leading bytes as [`bytes::Bytes`].
+pub trait SplitBuffer: crate::Buffer + Default {
+    /// Remove the given amount of bytes from the start of the buffer and
+    /// return them.
+    fn split_to_bytes(&mut self, bytes: usize) -> bytes::Bytes;
+}
+
+impl SplitBuffer for Vec<u8> {
+    fn split_to_bytes(&mut self, bytes: usize) -> bytes::Bytes {
+        self.drain(..bytes).collect()
+    }
+}
+
+/// Zero-copy: the returned [`bytes::Bytes`] share the allocation with
+/// the buffer.
+impl SplitBuffer for bytes::BytesMut {
+    fn split_to_bytes(&mut self, bytes: usize) -> bytes::Bytes {
+        self.split_to(bytes).freeze()
+    }
+}
+
+/// The buffer presented to the inner decoder.
+///
+/// Advancing this buffer does not drop the data, but merely records
+/// the amount of bytes the inner decoder has consumed, so that these bytes can
+/// be split off afterwards.
+#[derive(Debug, Default)]
+pub struct Lookahead<Buffer> {
+    /// The actual buffer.
+    inner: Buffer,
+    /// The amount of bytes consumed from the actual buffer so far.
+    consumed: usize,
+}
+
+impl<Buffer> crate::Buffer for Lookahead<Buffer>
+where
+    Buffer: crate::Buffer,
+{
+    fn append(&mut self, chunk: &[u8]) {
+        self.inner.append(chunk)
+    }
+
+    fn view(&self) -> &[u8] {
+        self.inner.view().get(self.consumed..).unwrap_or_default()
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn advance(&mut self, bytes: usize) {
+        self.consumed += bytes.min(self.len());
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn len(&self) -> usize {
+        self.inner.len() - self.consumed
+    }
+}
+
+/// The decoder that wraps any other decoder and returns the exact bytes it
+/// has consumed along with each decoded value.
+///
+/// The inner decoder works with a [`Lookahead`] buffer, so any decoder that is
+/// generic over the [`crate::Buffer`] can be used.
+/// With [`bytes::BytesMut`] as the buffer no data is copied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoder<T> {
+    /// The inner decoder.
+    pub inner: T,
+}
+
+impl<T> Decoder<T> {
+    /// Create a new [`Decoder`] wrapping the given decoder.
+    pub const fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Run the given decoding function over the [`Lookahead`] for the `input`,
+    /// and split off the consumed bytes.
+    fn decode_with<Buffer, F>(
+        &mut self,
+        input: &mut Buffer,
+        f: F,
+    ) -> Result<(InnerValue<T, Buffer>, bytes::Bytes), InnerDecodeError<T, Buffer>>
+    where
+        Buffer: SplitBuffer,
+        T: crate::Decoder<Lookahead<Buffer>>,
+        F: FnOnce(&mut T, &mut Lookahead<Buffer>) -> InnerResult<T, Buffer>,
+    {
+        let mut lookahead = Lookahead {
+            inner: std::mem::take(input),
+            consumed: 0,
+        };
+        let result = f(&mut self.inner, &mut lookahead);
+        *input = lookahead.inner;
+        match result {
+            Ok(value) => Ok((value, input.split_to_bytes(lookahead.consumed))),
+            Err(err) => {
+                // The inner decoder might have consumed some data before
+                // failing, so make sure it is dropped as it would have been
+                // without the wrapper.
+                input.advance(lookahead.consumed);
+                Err(err)
+            }
+        }
+    }
+}
+
+impl<T, Buffer> crate::Decoder<Buffer> for Decoder<T>
+where
+    Buffer: SplitBuffer,
+    T: crate::Decoder<Lookahead<Buffer>>,
+{
+    type Value = (InnerValue<T, Buffer>, bytes::Bytes);
+    type Error = InnerError<T, Buffer>;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        self.decode_with(input, crate::Decoder::decode)
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        self.decode_with(input, crate::Decoder::decode_eof)
+    }
+
+    fn reset(&mut self) {
+        crate::Decoder::<Lookahead<Buffer>>::reset(&mut self.inner);
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn make_state<Buffer: Default>(
+    ) -> crate::State<Decoder<crate::decoder::serde_json::Decoder<TO>>, Buffer> {
+        crate::State::new(
+            Decoder::new(crate::decoder::serde_json::Decoder::new()),
+            Buffer::default(),
+        )
+    }
+
+    #[test]
+    fn test_chunked() {
+        let mut dec = make_state::<Vec<u8>>();
+
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"} {"fie"#);
+            let (value, raw) = stream.next().unwrap().unwrap();
+            assert_eq!(value.field, "val0");
+            assert_eq!(raw, &br#"{"field": "val0"}"#[..]);
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.process_next_chunk(br#"ld": "val1"}"#);
+            let (value, raw) = stream.next().unwrap().unwrap();
+            assert_eq!(value.field, "val1");
+            assert_eq!(raw, &br#" {"field": "val1"}"#[..]);
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_bytes_mut_zero_copy() {
+        let mut dec = make_state::<bytes::BytesMut>();
+
+        let data_ptr;
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"}{"#);
+            data_ptr = stream.buffer().as_ptr();
+            let (value, raw) = stream.next().unwrap().unwrap();
+            assert_eq!(value.field, "val0");
+            assert_eq!(raw, &br#"{"field": "val0"}"#[..]);
+            assert_eq!(raw.as_ptr(), data_ptr);
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), &b"{"[..]);
+    }
+
+    #[test]
+    fn test_error_drops_nothing() {
+        let mut dec = make_state::<Vec<u8>>();
+
+        {
+            let mut stream = dec.process_next_chunk(b"qwerty");
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), b"qwerty");
+    }
+}
@@ -0,0 +1,115 @@
+//! [`rmp_serde`] integration (i.e. msgpack).
+
+use std::marker::PhantomData;
+
+/// The decoder for [`rmp_serde`].
+///
+/// Decodes the stream of concatenated msgpack values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoder<T> {
+    /// The marker for data type to decode.
+    pub data_type: PhantomData<T>,
+}
+
+impl<T> Decoder<T> {
+    /// Create a new [`Decoder`].
+    pub const fn new() -> Self {
+        Self {
+            data_type: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for Decoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, Buffer> crate::Decoder<Buffer> for Decoder<T>
+where
+    T: for<'de> serde::de::Deserialize<'de>,
+    Buffer: crate::Buffer,
+{
+    type Value = T;
+    type Error = rmp_serde::decode::Error;
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        let buf = input.view();
+        let mut rest = buf;
+        let mut deserializer = rmp_serde::Deserializer::new(&mut rest);
+        match T::deserialize(&mut deserializer) {
+            Ok(value) => {
+                input.advance(buf.len() - rest.len());
+                Ok(value)
+            }
+            Err(err) if is_eof(&err) => Err(crate::DecodeError::NeedMoreData),
+            Err(err) => Err(crate::DecodeError::Other(err)),
+        }
+    }
+}
+
+/// Check whether the error is caused by the incomplete data.
+fn is_eof(err: &rmp_serde::decode::Error) -> bool {
+    match err {
+        rmp_serde::decode::Error::InvalidMarkerRead(err)
+        | rmp_serde::decode::Error::InvalidDataRead(err) => {
+            err.kind() == std::io::ErrorKind::UnexpectedEof
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn encode(field: &str) -> Vec<u8> {
+        rmp_serde::to_vec_named(&TestObject {
+            field: field.into(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_chunked() {
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new());
+
+        let mut data = encode("val0");
+        data.extend(encode("val1"));
+
+        let mut values = Vec::new();
+        for chunk in data.chunks(3) {
+            for value in dec.process_next_chunk(chunk) {
+                values.push(value.unwrap().field);
+            }
+        }
+        assert_eq!(values, ["val0", "val1"]);
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_rubbish() {
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new());
+
+        {
+            let mut stream = dec.process_next_chunk(&[0xc1]);
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), [0xc1]);
+    }
+}
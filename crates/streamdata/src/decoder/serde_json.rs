@@ -2,11 +2,29 @@
 
 use std::marker::PhantomData;
 
+use super::json_scanner::{Exceeded, Limits, Scanner};
+
 /// The decoder for [`serde_json`].
+///
+/// The buffered data is scanned for the end of the value incrementally, so
+/// the value is only parsed once it is complete, no matter how many chunks
+/// it arrives in.
+/// The incomplete value is still checked for the syntax errors, but only
+/// each time the amount of its data doubles, so that the errors are detected
+/// early while the decoding stays linear.
+///
+/// The size and the nesting depth of the values can be limited (see
+/// [`Decoder::with_max_size`] and [`Decoder::with_max_depth`]), in which
+/// case the error is reported as soon as the limit is exceeded, without
+/// waiting for the rest of the value.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Decoder<T> {
     /// The marker for data type to decode.
     pub data_type: PhantomData<T>,
+    /// The limits on the values.
+    limits: Limits,
+    /// The scanner of the value being buffered.
+    scanner: Scanner,
 }
 
 impl<T> Decoder<T> {
@@ -14,6 +32,61 @@ impl<T> Decoder<T> {
     pub const fn new() -> Self {
         Self {
             data_type: PhantomData,
+            limits: Limits::NONE,
+            scanner: Scanner::new(),
+        }
+    }
+
+    /// Limit the size of a single value in bytes, not counting
+    /// the whitespace preceding it.
+    pub const fn with_max_size(mut self, max_size: usize) -> Self {
+        self.limits.max_size = Some(max_size);
+        self
+    }
+
+    /// Limit the nesting depth of the objects and arrays in a single value.
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.limits.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Set all the limits at once.
+    pub(super) const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Scan the buffered data for the end of the value.
+    fn scan(&mut self, buf: &[u8]) -> Result<Option<usize>, crate::DecodeError<Error>> {
+        self.scanner.scan(buf, self.limits).map_err(|exceeded| {
+            self.scanner = Scanner::new();
+            crate::DecodeError::Other(exceeded.into())
+        })
+    }
+
+    /// Parse the value at the start of the buffer, once it is known to be
+    /// complete.
+    ///
+    /// The parser is given the data following the value as well, so that
+    /// the garbage right after a number (i.e. `12x`) is rejected along with
+    /// the number.
+    fn parse<Buffer>(&mut self, input: &mut Buffer) -> Result<T, crate::DecodeError<Error>>
+    where
+        T: for<'de> serde::de::Deserialize<'de>,
+        Buffer: crate::Buffer,
+    {
+        self.scanner = Scanner::new();
+        let buf = input.view();
+        let mut iter = serde_json::Deserializer::from_slice(buf).into_iter::<T>();
+        let item = iter.next();
+        match item {
+            None => Err(crate::DecodeError::NeedMoreData),
+            Some(Ok(value)) => {
+                input.advance(iter.byte_offset());
+                Ok(value)
+            }
+            Some(Err(err)) if err.is_eof() => Err(crate::DecodeError::NeedMoreData),
+            Some(Err(err)) => Err(crate::DecodeError::Other(Error::Json(err))),
         }
     }
 }
@@ -30,21 +103,371 @@ where
     Buffer: crate::Buffer,
 {
     type Value = T;
-    type Error = serde_json::Error;
+    type Error = Error;
 
     fn decode(
         &mut self,
         input: &mut Buffer,
     ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
-        let buf = input.view();
-        let mut iter = serde_json::Deserializer::from_slice(buf).into_iter::<T>();
+        match self.scan(input.view())? {
+            Some(_) => self.parse(input),
+            None => {
+                check_incomplete(&mut self.scanner, input.view())?;
+                Err(crate::DecodeError::NeedMoreData)
+            }
+        }
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        match self.scan(input.view())? {
+            Some(_) => self.parse(input),
+            // The top-level number is terminated by the end of input.
+            None if self.scanner.in_number() => self.parse(input),
+            None => Err(crate::DecodeError::NeedMoreData),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.scanner = Scanner::new();
+    }
+}
+
+/// The error that can occur when decoding with the [`Decoder`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The data is not a valid JSON, or does not match the data type.
+    Json(serde_json::Error),
+    /// The value is larger than the limit.
+    TooLarge {
+        /// The maximum size of the value in bytes.
+        max_size: usize,
+    },
+    /// The value is nested deeper than the limit.
+    TooDeep {
+        /// The maximum nesting depth of the value.
+        max_depth: usize,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "{err}"),
+            Self::TooLarge { max_size } => {
+                write!(f, "value exceeds the size limit of {max_size} bytes")
+            }
+            Self::TooDeep { max_depth } => {
+                write!(f, "value exceeds the nesting depth limit of {max_depth}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Json(err) => Some(err),
+            Self::TooLarge { .. } | Self::TooDeep { .. } => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<Exceeded> for Error {
+    fn from(exceeded: Exceeded) -> Self {
+        match exceeded {
+            Exceeded::Size(max_size) => Self::TooLarge { max_size },
+            Exceeded::Depth(max_depth) => Self::TooDeep { max_depth },
+        }
+    }
+}
+
+/// Check the incomplete value for the syntax errors, unless the amount of
+/// its data has not doubled since the last check.
+///
+/// The numbers are left alone, since their prefixes (i.e. `-`) are not
+/// necessarily valid on their own.
+fn check_incomplete(scanner: &mut Scanner, buf: &[u8]) -> Result<(), crate::DecodeError<Error>> {
+    if scanner.in_number() || buf.len() < scanner.checked.saturating_mul(2) {
+        return Ok(());
+    }
+    scanner.checked = buf.len();
+    let mut iter = serde_json::Deserializer::from_slice(buf).into_iter::<serde::de::IgnoredAny>();
+    match iter.next() {
+        Some(Err(err)) if !err.is_eof() => {
+            *scanner = Scanner::new();
+            Err(crate::DecodeError::Other(Error::Json(err)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The decoder for [`serde_json`] that decodes the elements of the top-level
+/// JSON arrays one by one, i.e. `[{...}, {...}, ...]`.
+///
+/// The consecutive arrays are decoded as a single stream of elements.
+/// Unlike the [`Decoder`], the value is only yielded once the delimiter that
+/// follows it is available, so the numbers split across the chunks are
+/// decoded correctly.
+///
+/// The elements are scanned incrementally and checked for the syntax errors
+/// the same way as with the [`Decoder`], and so are their sizes and nesting
+/// depths limited (see [`ArrayDecoder::with_max_size`] and
+/// [`ArrayDecoder::with_max_depth`]).
+///
+/// After the reset (see [`crate::Recovery`]) inside an array, the decoding
+/// resumes with the next element, skipping the `,` preceding it, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayDecoder<T> {
+    /// The marker for data type to decode.
+    pub data_type: PhantomData<T>,
+    /// The position in the array.
+    position: ArrayPosition,
+    /// The limits on the elements.
+    limits: Limits,
+    /// The scanner of the element being buffered.
+    scanner: Scanner,
+}
+
+/// The position in the array of the [`ArrayDecoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArrayPosition {
+    /// Outside of the array, expecting `[`.
+    Outside,
+    /// Right after the `[`, expecting an element or `]`.
+    Start,
+    /// After the `,`, expecting an element.
+    Element,
+    /// After the reset inside the array, expecting an element, `,` or `]`.
+    Resumed,
+}
+
+impl<T> ArrayDecoder<T> {
+    /// Create a new [`ArrayDecoder`].
+    pub const fn new() -> Self {
+        Self {
+            data_type: PhantomData,
+            position: ArrayPosition::Outside,
+            limits: Limits::NONE,
+            scanner: Scanner::new(),
+        }
+    }
+
+    /// Limit the size of a single element in bytes, not counting
+    /// the whitespace preceding it.
+    pub const fn with_max_size(mut self, max_size: usize) -> Self {
+        self.limits.max_size = Some(max_size);
+        self
+    }
+
+    /// Limit the nesting depth of the objects and arrays in a single element,
+    /// not counting the top-level array itself.
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.limits.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Set all the limits at once.
+    pub(super) const fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Scan the buffered element for its end.
+    fn scan(&mut self, buf: &[u8]) -> Result<Option<usize>, crate::DecodeError<Error>> {
+        self.scanner.scan(buf, self.limits).map_err(|exceeded| {
+            self.scanner = Scanner::new();
+            crate::DecodeError::Other(exceeded.into())
+        })
+    }
+}
+
+impl<T> Default for ArrayDecoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Make the syntax error with the given message.
+fn syntax_error(msg: &str) -> crate::DecodeError<Error> {
+    crate::DecodeError::Other(Error::Json(serde::de::Error::custom(msg)))
+}
+
+/// Return the amount of the leading JSON whitespace bytes.
+fn whitespace_len(buf: &[u8]) -> usize {
+    buf.iter()
+        .take_while(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+        .count()
+}
+
+impl<T, Buffer> crate::Decoder<Buffer> for ArrayDecoder<T>
+where
+    T: for<'de> serde::de::Deserialize<'de>,
+    Buffer: crate::Buffer,
+{
+    type Value = T;
+    type Error = Error;
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        loop {
+            let buf = input.view();
+            let skip = whitespace_len(buf);
+            let Some(&next) = buf.get(skip) else {
+                // Only the whitespace, if anything.
+                input.advance(skip);
+                return Err(crate::DecodeError::NeedMoreData);
+            };
+
+            match (self.position, next) {
+                (ArrayPosition::Outside, b'[') => {
+                    input.advance(skip + 1);
+                    self.position = ArrayPosition::Start;
+                    continue;
+                }
+                (ArrayPosition::Outside, _) => return Err(syntax_error("expected `[`")),
+                (ArrayPosition::Resumed, b',') => {
+                    input.advance(skip + 1);
+                    self.position = ArrayPosition::Element;
+                    continue;
+                }
+                (ArrayPosition::Start | ArrayPosition::Resumed, b']') => {
+                    input.advance(skip + 1);
+                    self.position = ArrayPosition::Outside;
+                    continue;
+                }
+                (ArrayPosition::Start | ArrayPosition::Element | ArrayPosition::Resumed, _) => {}
+            }
+
+            let rest = &buf[skip..];
+            let Some(end) = self.scan(rest)? else {
+                check_incomplete(&mut self.scanner, rest)?;
+                return Err(crate::DecodeError::NeedMoreData);
+            };
+            // Only parse the element once the delimiter following it is
+            // available; the literals are always parsed right away, as their
+            // end is not scanned.
+            let tail = &rest[end..];
+            if end < rest.len() && whitespace_len(tail) == tail.len() {
+                return Err(crate::DecodeError::NeedMoreData);
+            }
+
+            let mut iter = serde_json::Deserializer::from_slice(rest).into_iter::<T>();
+            let value = match iter.next() {
+                None => return Err(crate::DecodeError::NeedMoreData),
+                Some(Ok(value)) => value,
+                Some(Err(err)) if err.is_eof() => return Err(crate::DecodeError::NeedMoreData),
+                Some(Err(err)) => {
+                    self.scanner = Scanner::new();
+                    return Err(crate::DecodeError::Other(Error::Json(err)));
+                }
+            };
+
+            let value_end = skip + iter.byte_offset();
+            let after = &buf[value_end..];
+            let delimiter_at = whitespace_len(after);
+            self.position = match after.get(delimiter_at) {
+                None => return Err(crate::DecodeError::NeedMoreData),
+                Some(b',') => ArrayPosition::Element,
+                Some(b']') => ArrayPosition::Outside,
+                Some(_) => {
+                    self.scanner = Scanner::new();
+                    return Err(syntax_error("expected `,` or `]`"));
+                }
+            };
+            self.scanner = Scanner::new();
+            input.advance(value_end + delimiter_at + 1);
+            return Ok(value);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.scanner = Scanner::new();
+        if self.position != ArrayPosition::Outside {
+            self.position = ArrayPosition::Resumed;
+        }
+    }
+}
+
+/// A family of types that can be deserialized borrowing from the data,
+/// for use with the [`LendingDecoder`].
+///
+/// Implement it for a marker type to decode your own `#[serde(borrow)]`
+/// types.
+pub trait Borrowed {
+    /// The type to deserialize, borrowing from the data.
+    type Value<'de>: serde::de::Deserialize<'de>;
+}
+
+/// The [`Borrowed`] family for `&str`.
+///
+/// Note that only the strings without escape sequences can be borrowed.
+#[derive(Debug)]
+pub struct Str;
+
+impl Borrowed for Str {
+    type Value<'de> = &'de str;
+}
+
+/// The [`Borrowed`] family for [`serde_json::value::RawValue`].
+#[derive(Debug)]
+pub struct RawValue;
+
+impl Borrowed for RawValue {
+    type Value<'de> = &'de serde_json::value::RawValue;
+}
+
+/// The lending decoder for [`serde_json`], that deserializes the values
+/// borrowing from the buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LendingDecoder<T> {
+    /// The marker for the family of the data types to decode.
+    pub data_type: PhantomData<T>,
+}
+
+impl<T> LendingDecoder<T> {
+    /// Create a new [`LendingDecoder`].
+    pub const fn new() -> Self {
+        Self {
+            data_type: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for LendingDecoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> crate::LendingDecoder for LendingDecoder<T>
+where
+    T: Borrowed,
+{
+    type Value<'buf> = <T as Borrowed>::Value<'buf>;
+    type Error = serde_json::Error;
+
+    fn decode<'buf>(
+        &mut self,
+        buf: &'buf [u8],
+    ) -> Result<(Self::Value<'buf>, usize), crate::DecodeError<Self::Error>> {
+        let mut iter = serde_json::Deserializer::from_slice(buf).into_iter::<Self::Value<'buf>>();
         let item = iter.next();
         match item {
             None => Err(crate::DecodeError::NeedMoreData),
-            Some(Ok(value)) => {
-                input.advance(iter.byte_offset());
-                Ok(value)
-            }
+            Some(Ok(value)) => Ok((value, iter.byte_offset())),
             Some(Err(err)) if err.is_eof() => Err(crate::DecodeError::NeedMoreData),
             Some(Err(err)) => Err(crate::DecodeError::Other(err)),
         }
@@ -67,8 +490,8 @@ mod tests {
         TO { field: uid.into() }
     }
 
-    fn assert_test_object(
-        tested_test_object: Option<Result<TO, serde_json::Error>>,
+    fn assert_test_object<E: std::fmt::Debug>(
+        tested_test_object: Option<Result<TO, E>>,
         expected_uid: &str,
     ) {
         let actual_to = tested_test_object
@@ -79,10 +502,7 @@ mod tests {
     }
 
     fn make_state<T>() -> crate::State<Decoder<T>, Vec<u8>> {
-        crate::State {
-            buffer: Vec::new(),
-            decoder: Decoder::default(),
-        }
+        crate::State::new(Decoder::default(), Vec::new())
     }
 
     #[test]
@@ -334,6 +754,598 @@ mod tests {
         assert_eq!(dec.finish().unwrap_err(), b"\n\n\n");
     }
 
+    #[test]
+    fn test_offsets() {
+        let mut dec = make_state::<TO>();
+
+        {
+            let mut stream = dec
+                .process_next_chunk(b"{\"field\":\"val0\"}\n{\"fie")
+                .with_offsets();
+            let decoded = stream.next().unwrap().unwrap();
+            assert_eq!(decoded.value, make_to("val0"));
+            assert_eq!((decoded.start, decoded.end), (0, 16));
+            assert_eq!(decoded.consumed_bytes, 16);
+            assert!(stream.next().is_none());
+            assert_eq!(stream.offset(), 16);
+        }
+
+        {
+            let mut stream = dec
+                .process_next_chunk(b"ld\":\"val1\"}qwerty")
+                .with_offsets();
+            let decoded = stream.next().unwrap().unwrap();
+            assert_eq!(decoded.value, make_to("val1"));
+            assert_eq!((decoded.start, decoded.end), (16, 33));
+            assert_eq!(decoded.consumed_bytes, 17);
+            assert!(stream.next().unwrap().is_err());
+            assert_eq!(stream.offset(), 33);
+        }
+
+        assert_eq!(dec.offset, 33);
+        assert_eq!(dec.finish().unwrap_err(), b"qwerty");
+    }
+
+    #[test]
+    fn test_buffer_limit_fail() {
+        let mut dec = make_state::<TO>().with_buffer_limit(crate::BufferLimit {
+            max_bytes: 16,
+            on_exceeded: crate::OnBufferLimitExceeded::Fail,
+        });
+
+        {
+            let mut stream = dec.try_process_next_chunk(br#"{"field": ""#).unwrap();
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.try_process_next_chunk(b"aaaaaaaa").unwrap();
+            assert!(stream.next().is_none());
+        }
+
+        let err = dec.try_process_next_chunk(br#"a"}"#).err().unwrap();
+        assert_eq!(
+            err,
+            crate::BufferLimitExceeded {
+                pending: 19,
+                chunk: 3,
+                max_bytes: 16,
+            }
+        );
+
+        assert_eq!(dec.finish().unwrap_err(), br#"{"field": "aaaaaaaa"#);
+    }
+
+    #[test]
+    fn test_buffer_limit_applies_to_undecoded_data() {
+        let mut dec = make_state::<u32>().with_buffer_limit(crate::BufferLimit {
+            max_bytes: 8,
+            on_exceeded: crate::OnBufferLimitExceeded::Fail,
+        });
+
+        let values: Vec<_> = dec
+            .try_process_next_chunk(b"1 2 3 4 5 6")
+            .unwrap()
+            .try_collect()
+            .unwrap();
+        assert_eq!(values, [1, 2, 3, 4, 5]);
+
+        let values: Vec<_> = dec
+            .try_process_next_chunk(b"7 8 9 10 11")
+            .unwrap()
+            .try_collect()
+            .unwrap();
+        assert_eq!(values, [67, 8, 9, 10]);
+    }
+
+    #[test]
+    fn test_buffer_limit_discard() {
+        let mut dec = make_state::<TO>()
+            .with_buffer_limit(crate::BufferLimit {
+                max_bytes: 16,
+                on_exceeded: crate::OnBufferLimitExceeded::Discard,
+            })
+            .with_recovery(crate::Recovery::SkipToNewline);
+
+        {
+            let mut stream = dec.try_process_next_chunk(br#"{"field": ""#).unwrap();
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.try_process_next_chunk(b"aaaaaaaa").unwrap();
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(dec.offset, 0);
+
+        {
+            let mut stream = dec
+                .try_process_next_chunk(b"aaa\"}\n{\"field\":\"val0\"}")
+                .unwrap();
+            // The rest of the dropped value is skipped by the recovery.
+            assert!(stream.next().unwrap().is_err());
+            assert_test_object(stream.next(), "val0");
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_byte_by_byte() {
+        let mut dec = make_state::<TO>();
+
+        let data = br#" {"field": "v}]\"{["} {"extra": [{}, "]"], "field": "\\"}"#;
+        let mut decoded = Vec::new();
+        for byte in data.chunks(1) {
+            decoded.extend(dec.process_next_chunk(byte).map(Result::unwrap));
+        }
+        assert_eq!(decoded, [make_to("v}]\"{["), make_to("\\")]);
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_numbers() {
+        let mut dec = make_state::<f64>();
+
+        {
+            let stream = dec.process_next_chunk(b"1 -");
+            let values: Vec<_> = stream.try_collect().unwrap();
+            assert_eq!(values, [1.0]);
+        }
+
+        {
+            let stream = dec.process_next_chunk(b"2");
+            let values: Vec<_> = stream.try_collect().unwrap();
+            assert!(values.is_empty());
+        }
+
+        {
+            // The number is only complete once followed by something else.
+            let stream = dec.process_next_chunk(b".5e1 3");
+            let values: Vec<_> = stream.try_collect().unwrap();
+            assert_eq!(values, [-25.0]);
+        }
+
+        {
+            let stream = dec.process_eof();
+            let values: Vec<_> = stream.try_collect().unwrap();
+            assert_eq!(values, [3.0]);
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_garbage_after_number() {
+        let mut dec = make_state::<f64>();
+
+        {
+            let mut stream = dec.process_next_chunk(b"12x");
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), b"12x");
+    }
+
+    #[test]
+    fn test_incomplete_syntax_error() {
+        let mut dec = make_state::<TO>();
+
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"}{"field" 1"#);
+            assert_test_object(stream.next(), "val0");
+            assert!(stream.next().unwrap().is_err());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), br#"{"field" 1"#);
+    }
+
+    #[test]
+    fn test_max_size() {
+        let mut dec = crate::State::new(Decoder::<TO>::new().with_max_size(20), Vec::new());
+
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"} {"field": "val1val1"}"#);
+            assert_test_object(stream.next(), "val0");
+            assert!(matches!(
+                stream.next(),
+                Some(Err(Error::TooLarge { max_size: 20 }))
+            ));
+            assert!(stream.next().is_none());
+        }
+
+        // The error is reported before the value is complete.
+        let mut dec = crate::State::new(Decoder::<TO>::new().with_max_size(20), Vec::new());
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"#);
+            assert!(stream.next().is_none());
+        }
+        {
+            let mut stream = dec.process_next_chunk(br#"val0val0"#);
+            assert!(matches!(
+                stream.next(),
+                Some(Err(Error::TooLarge { max_size: 20 }))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_max_size_whitespace() {
+        let mut dec = crate::State::new(Decoder::<TO>::new().with_max_size(17), Vec::new());
+
+        // The keepalive newlines do not count towards the limit.
+        for _ in 0..16 {
+            assert!(dec.process_next_chunk(b"\n").next().is_none());
+        }
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"}"#);
+            assert_test_object(stream.next(), "val0");
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let mut dec = crate::State::new(
+            Decoder::<serde_json::Value>::new().with_max_depth(2),
+            Vec::new(),
+        )
+        .with_recovery(crate::Recovery::SkipToNewline);
+
+        let stream = dec.process_next_chunk(b"[[1], {}]\n[[[\n{\"a\": [{}]}\n");
+        let results: Vec<_> = stream
+            .map(|result| result.map_err(|err| err.to_string()))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                Ok(serde_json::json!([[1], {}])),
+                Err("value exceeds the nesting depth limit of 2".to_owned()),
+                Err("value exceeds the nesting depth limit of 2".to_owned()),
+            ]
+        );
+    }
+
+    /// Feed a single array of strings of a given size to the decoder in
+    /// small chunks, returning the time it took.
+    fn decode_large_document(size: usize) -> std::time::Duration {
+        const CHUNK_SIZE: usize = 1024;
+
+        let mut data = b"[".to_vec();
+        while data.len() < size {
+            data.extend_from_slice(br#""{[\"]}","#);
+        }
+        data.extend_from_slice(br#""end"]"#);
+
+        let mut dec = make_state::<Vec<String>>();
+        let started_at = std::time::Instant::now();
+        let mut decoded = Vec::new();
+        for chunk in data.chunks(CHUNK_SIZE) {
+            decoded.extend(dec.process_next_chunk(chunk).map(Result::unwrap));
+        }
+        let elapsed = started_at.elapsed();
+
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].last().unwrap(), "end");
+        assert!(dec.finish().is_ok());
+        elapsed
+    }
+
+    #[test]
+    fn test_large_document_is_linear() {
+        let small = decode_large_document(512 * 1024);
+        let large = decode_large_document(5 * 1024 * 1024);
+
+        // Parsing the whole buffer after every chunk would make this 100
+        // times slower rather than 10.
+        assert!(
+            large < small * 40,
+            "512 KB took {small:?}, 5 MB took {large:?}"
+        );
+    }
+
+    fn collect_fields<Buffer: crate::Buffer>(
+        stream: crate::AvailableIter<'_, Decoder<TO>, Buffer>,
+    ) -> Vec<Result<String, ()>> {
+        stream
+            .map(|result| result.map(|value| value.field).map_err(|_| ()))
+            .collect()
+    }
+
+    #[test]
+    fn test_recovery_skip_to_newline() {
+        let mut dec = make_state::<TO>().with_recovery(crate::Recovery::SkipToNewline);
+
+        let stream = dec.process_next_chunk(b"{\"field\": \"val0\"}\n{\"field\": 1}\n{\"fie");
+        assert_eq!(collect_fields(stream), [Ok("val0".to_owned()), Err(())]);
+
+        let stream = dec.process_next_chunk(b"ld\": \"val2\"}\nqwe");
+        assert_eq!(collect_fields(stream), [Ok("val2".to_owned()), Err(())]);
+
+        // The resynchronization continues with the next chunk.
+        let stream = dec.process_next_chunk(b"rty");
+        assert_eq!(collect_fields(stream), []);
+        assert!(dec.is_empty());
+
+        let stream = dec.process_next_chunk(b"\n{\"field\": \"val4\"}");
+        assert_eq!(collect_fields(stream), [Ok("val4".to_owned())]);
+
+        assert_eq!(dec.offset, 73);
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_recovery_skip_to_pattern() {
+        let mut dec =
+            make_state::<TO>().with_recovery(crate::Recovery::SkipToPattern(b"{\"".to_vec()));
+
+        // The pattern is split across the chunks.
+        let stream = dec.process_next_chunk(br#"{"field": "val0"}{"bad" 1 {"#);
+        assert_eq!(collect_fields(stream), [Ok("val0".to_owned()), Err(())]);
+        assert_eq!(dec.buffer, b"{");
+
+        let stream = dec.process_next_chunk(br#""field": "val2"}"#);
+        assert_eq!(collect_fields(stream), [Ok("val2".to_owned())]);
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_recovery_skip_bytes() {
+        let mut dec = make_state::<TO>().with_recovery(crate::Recovery::SkipBytes(8));
+
+        let stream = dec.process_next_chunk(br#"qwe"#);
+        assert_eq!(collect_fields(stream), [Err(())]);
+        assert!(dec.is_empty());
+
+        let stream = dec.process_next_chunk(br#"rtyqw{"field": "val0"}"#);
+        assert_eq!(collect_fields(stream), [Ok("val0".to_owned())]);
+        assert_eq!(dec.offset, 25);
+
+        // The data being skipped ends with the input.
+        let stream = dec.process_next_chunk(br#"qwe"#);
+        assert_eq!(collect_fields(stream), [Err(())]);
+        let stream = dec.process_eof();
+        assert_eq!(collect_fields(stream), []);
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_recovery_makes_progress() {
+        for recovery in [
+            crate::Recovery::SkipBytes(0),
+            crate::Recovery::SkipToPattern(Vec::new()),
+        ] {
+            let mut dec = make_state::<u32>().with_recovery(recovery);
+
+            let values = dec
+                .process_next_chunk(b"x 1 2 ")
+                .map(|result| result.map_err(|_| ()))
+                .collect::<Vec<_>>();
+            assert_eq!(values, [Err(()), Ok(1), Ok(2)]);
+            assert_eq!(dec.finish().unwrap_err(), b" ");
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+    struct BorrowedTestObject<'a> {
+        pub field: &'a str,
+    }
+
+    struct BorrowedTestObjectFamily;
+
+    impl Borrowed for BorrowedTestObjectFamily {
+        type Value<'de> = BorrowedTestObject<'de>;
+    }
+
+    #[test]
+    fn test_lending() {
+        let mut dec = crate::State::new(
+            LendingDecoder::<BorrowedTestObjectFamily>::new(),
+            Vec::new(),
+        );
+
+        {
+            let mut stream = dec.process_next_chunk_lending(br#"{"field": "val0"} {"fie"#);
+            let value = stream.next().unwrap().unwrap();
+            assert_eq!(value.field, "val0");
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(dec.buffer, br#" {"fie"#);
+
+        {
+            let mut stream = dec.process_next_chunk_lending(br#"ld": "val1"}{"field": "val2"}"#);
+            assert_eq!(stream.next().unwrap().unwrap().field, "val1");
+            assert_eq!(stream.next().unwrap().unwrap().field, "val2");
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.offset, 52);
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_lending_buffer_limit() {
+        let mut dec = crate::State::new(
+            LendingDecoder::<BorrowedTestObjectFamily>::new(),
+            Vec::new(),
+        )
+        .with_buffer_limit(crate::BufferLimit {
+            max_bytes: 8,
+            on_exceeded: crate::OnBufferLimitExceeded::Fail,
+        });
+
+        {
+            let mut stream = dec
+                .try_process_next_chunk_lending(br#"{"field": "val0"} {"fie"#)
+                .unwrap();
+            assert_eq!(stream.next().unwrap().unwrap().field, "val0");
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.try_process_next_chunk_lending(br#"ld": "#).unwrap();
+            assert!(stream.next().is_none());
+        }
+
+        let err = dec
+            .try_process_next_chunk_lending(b"\"val1\"}")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            crate::BufferLimitExceeded {
+                pending: 11,
+                chunk: 7,
+                max_bytes: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn test_lending_raw_value() {
+        let mut dec = crate::State::new(LendingDecoder::<RawValue>::new(), Vec::new());
+
+        {
+            let mut stream = dec.process_next_chunk_lending(br#"{"a": [1, 2]} "str" qwerty"#);
+            assert_eq!(stream.next().unwrap().unwrap().get(), r#"{"a": [1, 2]}"#);
+            assert_eq!(stream.next().unwrap().unwrap().get(), r#""str""#);
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+            assert_eq!(stream.offset(), 19);
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), b" qwerty");
+    }
+
+    #[test]
+    fn test_lending_str() {
+        let mut dec = crate::State::new(LendingDecoder::<Str>::new(), Vec::new());
+
+        {
+            let mut stream = dec.process_next_chunk_lending(br#""val0""val"#);
+            assert_eq!(stream.next().unwrap().unwrap(), "val0");
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.process_eof_lending();
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), br#""val"#);
+    }
+
+    #[test]
+    fn test_array() {
+        let mut dec = crate::State::new(ArrayDecoder::<u32>::new(), Vec::new());
+
+        let data = b" [ 12 ,3,\n45 ] [] [6]";
+
+        // Every split point, including the ones splitting the numbers.
+        let mut values = Vec::new();
+        for chunk in data.chunks(1) {
+            values.extend(dec.process_next_chunk(chunk).map(Result::unwrap));
+        }
+        assert_eq!(values, [12, 3, 45, 6]);
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_array_mixed_elements() {
+        let mut dec = crate::State::new(ArrayDecoder::<serde_json::Value>::new(), Vec::new());
+
+        let data = br#"[true, -1.5e2 , "a\"]" ,{"b": [null, false]}, null ,[]]"#;
+
+        let mut values = Vec::new();
+        for chunk in data.chunks(1) {
+            values.extend(dec.process_next_chunk(chunk).map(Result::unwrap));
+        }
+        assert_eq!(
+            values,
+            [
+                serde_json::json!(true),
+                serde_json::json!(-150.0),
+                serde_json::json!("a\"]"),
+                serde_json::json!({"b": [null, false]}),
+                serde_json::json!(null),
+                serde_json::json!([]),
+            ]
+        );
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_array_errors() {
+        let mut dec = crate::State::new(ArrayDecoder::<TO>::new(), Vec::new());
+        {
+            let mut stream = dec.process_next_chunk(br#"{"field": "val0"}"#);
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+        }
+
+        let mut dec = crate::State::new(ArrayDecoder::<TO>::new(), Vec::new());
+        {
+            let mut stream = dec.process_next_chunk(br#"[{"field": "val0"}, {"field": "val1"} x"#);
+            assert_test_object(stream.next(), "val0");
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(dec.finish().unwrap_err(), br#" {"field": "val1"} x"#);
+    }
+
+    #[test]
+    fn test_array_limits() {
+        let mut dec = crate::State::new(
+            ArrayDecoder::<serde_json::Value>::new()
+                .with_max_size(8)
+                .with_max_depth(1),
+            Vec::new(),
+        )
+        .with_recovery(crate::Recovery::SkipToNewline);
+
+        let stream = dec.process_next_chunk(b"[\n  [1, 2],\n  \"123456789\",\n  [[]],\n  {}\n]");
+        let results: Vec<_> = stream
+            .map(|result| result.map_err(|err| err.to_string()))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                Ok(serde_json::json!([1, 2])),
+                Err("value exceeds the size limit of 8 bytes".to_owned()),
+                Err("value exceeds the nesting depth limit of 1".to_owned()),
+                Ok(serde_json::json!({})),
+            ]
+        );
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_array_recovery() {
+        let mut dec = crate::State::new(ArrayDecoder::<TO>::new(), Vec::new())
+            .with_recovery(crate::Recovery::SkipToNewline);
+
+        let data =
+            b"[{\"field\": \"val0\"},\n{\"field\": 1},\n{\"field\": \"val2\"}\n,{\"field\": 3}\n]";
+        let mut stream = dec.process_next_chunk(data);
+        assert_test_object(stream.next(), "val0");
+        assert!(stream.next().unwrap().is_err());
+        assert_test_object(stream.next(), "val2");
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+
+        assert!(dec.finish().is_ok());
+    }
+
     #[test]
     fn test_memory_usage() {
         let mut dec = make_state::<TO>();
@@ -0,0 +1,785 @@
+//! Stream format detection from the leading bytes of the data.
+//!
+//! The JSON-based formats are always supported, while the rest depend on
+//! the cargo features: `rmp-serde` for msgpack, `ciborium` for CBOR and
+//! `gzip` for the gzip-wrapped data.
+//! The formats that are detected but not enabled are reported as
+//! [`Error::Unsupported`].
+
+#[cfg(feature = "gzip")]
+use crate::Buffer as _;
+
+use super::json_scanner::Limits;
+
+/// The amount of leading bytes to look at before settling on the plain JSON
+/// when no newline is found.
+const MAX_SNIFF_LEN: usize = 64 * 1024;
+
+/// The default limit on the amount of the undecoded decompressed data.
+#[cfg(feature = "gzip")]
+const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+
+/// The amount of the compressed data to decompress at a time, which bounds
+/// the amount of the decompressed data produced at once.
+#[cfg(feature = "gzip")]
+const DECOMPRESS_CHUNK_SIZE: usize = 1024;
+
+/// The stream format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// The JSON values, possibly concatenated.
+    Json,
+    /// The newline-delimited JSON values.
+    Ndjson,
+    /// The elements of the top-level JSON array.
+    JsonArray,
+    /// The gzip-compressed data of some other format.
+    Gzip,
+    /// The concatenated msgpack values.
+    MessagePack,
+    /// The CBOR sequence.
+    Cbor,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Json => "JSON",
+            Self::Ndjson => "NDJSON",
+            Self::JsonArray => "JSON array",
+            Self::Gzip => "gzip",
+            Self::MessagePack => "MessagePack",
+            Self::Cbor => "CBOR",
+        })
+    }
+}
+
+/// Detect the format from the leading bytes of the stream.
+///
+/// Returns `Ok(None)` if more data is needed to decide.
+///
+/// The binary formats are told apart by the first byte, assuming the stream
+/// consists of the maps or the arrays: the msgpack maps and arrays are
+/// `0x80..=0x9f` and `0xdc..=0xdf`, and the CBOR maps are `0xa0..=0xbf`.
+/// The CBOR arrays overlap with the msgpack ones, so the CBOR streams
+/// starting with an array are only detected with the self-described CBOR
+/// tag (`0xd9d9f7`).
+///
+/// The JSON stream is considered NDJSON if its first line holds a complete
+/// JSON value.
+pub fn detect(data: &[u8], eof: bool) -> Result<Option<Format>, Error> {
+    /// The self-described CBOR tag.
+    const CBOR_TAG: &[u8] = &[0xd9, 0xd9, 0xf7];
+    /// The gzip magic.
+    const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+    let Some(&first) = data.first() else {
+        return Ok(None);
+    };
+    let prefix = match first {
+        0x1f => GZIP_MAGIC,
+        0xd9 => CBOR_TAG,
+        _ => &[],
+    };
+    if data.len() < prefix.len() && prefix.starts_with(data) {
+        return if eof {
+            Err(Error::Unrecognized)
+        } else {
+            Ok(None)
+        };
+    }
+
+    let format = match first {
+        0x1f if data.starts_with(GZIP_MAGIC) => Format::Gzip,
+        0xd9 if data.starts_with(CBOR_TAG) => Format::Cbor,
+        0x80..=0x9f | 0xdc..=0xdf => Format::MessagePack,
+        0xa0..=0xbf => Format::Cbor,
+        _ => return detect_json(data, eof),
+    };
+    Ok(Some(format))
+}
+
+/// Detect the JSON-based format.
+fn detect_json(data: &[u8], eof: bool) -> Result<Option<Format>, Error> {
+    let start = data
+        .iter()
+        .position(|byte| !matches!(byte, b' ' | b'\t' | b'\n' | b'\r'));
+    let Some(start) = start else {
+        // Only the whitespace so far.
+        return Ok(None);
+    };
+    let rest = data.get(start..).unwrap_or_default();
+
+    match rest.first() {
+        Some(b'[') => return Ok(Some(Format::JsonArray)),
+        Some(b'{' | b'"' | b'-' | b'0'..=b'9' | b't' | b'f' | b'n') => {}
+        _ => return Err(Error::Unrecognized),
+    }
+
+    let Some(line_end) = rest.iter().position(|&byte| byte == b'\n') else {
+        if eof || rest.len() >= MAX_SNIFF_LEN {
+            return Ok(Some(Format::Json));
+        }
+        return Ok(None);
+    };
+    let line = rest.get(..line_end).unwrap_or_default();
+    if serde_json::from_slice::<serde::de::IgnoredAny>(line).is_ok() {
+        return Ok(Some(Format::Ndjson));
+    }
+    Ok(Some(Format::Json))
+}
+
+/// The decoder that detects the format of the stream and then decodes
+/// the values with the matching built-in decoder.
+///
+/// Returns [`crate::DecodeError::NeedMoreData`] until there is enough data
+/// to detect the format (see [`detect`]), and keeps using the detected
+/// format for the rest of the stream.
+/// See [`Decoder::format`] for the detected format.
+///
+/// Unlike with the [`crate::decoder::serde_json::Decoder`], the trailing
+/// whitespace of the JSON stream is dropped at the end of input.
+///
+/// The gzip-wrapped data is decompressed as the values are decoded, and
+/// the amount of the decompressed data pending decoding is limited (see
+/// [`Decoder::with_max_decompressed_size`]).
+/// The recovery (see [`crate::Recovery`]) is not possible within
+/// the compressed stream, so after an error in the gzip-wrapped data the rest
+/// of the stream is skipped.
+#[derive(Debug)]
+pub struct Decoder<T> {
+    /// The decoder of the detected format, if any.
+    detected: Option<Detected<T>>,
+    /// The limits on the JSON values.
+    limits: Limits,
+    /// The limit on the amount of the undecoded decompressed data.
+    #[cfg(feature = "gzip")]
+    max_decompressed_size: usize,
+}
+
+/// The decoder of the detected format.
+#[derive(Debug)]
+enum Detected<T> {
+    /// The JSON or NDJSON decoder.
+    Json(Format, crate::decoder::serde_json::Decoder<T>),
+    /// The JSON array decoder.
+    JsonArray(crate::decoder::serde_json::ArrayDecoder<T>),
+    /// The gzip-wrapped data decoder.
+    #[cfg(feature = "gzip")]
+    Gzip(Box<Gunzip<T>>),
+    /// The msgpack decoder.
+    #[cfg(feature = "rmp-serde")]
+    MessagePack(crate::decoder::rmp_serde::Decoder<T>),
+    /// The CBOR decoder.
+    #[cfg(feature = "ciborium")]
+    Cbor(crate::decoder::ciborium::Decoder<T>),
+}
+
+impl<T> Detected<T> {
+    /// Create the decoder for the given format, with the settings of
+    /// the given [`Decoder`].
+    fn new(format: Format, settings: &Decoder<T>) -> Result<Self, Error> {
+        Ok(match format {
+            Format::Json | Format::Ndjson => Self::Json(
+                format,
+                crate::decoder::serde_json::Decoder::new().with_limits(settings.limits),
+            ),
+            Format::JsonArray => Self::JsonArray(
+                crate::decoder::serde_json::ArrayDecoder::new().with_limits(settings.limits),
+            ),
+            #[cfg(feature = "gzip")]
+            Format::Gzip => Self::Gzip(Box::new(Gunzip::new(settings))),
+            #[cfg(feature = "rmp-serde")]
+            Format::MessagePack => Self::MessagePack(crate::decoder::rmp_serde::Decoder::new()),
+            #[cfg(feature = "ciborium")]
+            Format::Cbor => Self::Cbor(crate::decoder::ciborium::Decoder::new()),
+            #[allow(unreachable_patterns)]
+            format => return Err(Error::Unsupported(format)),
+        })
+    }
+
+    /// The format of the decoder.
+    const fn format(&self) -> Format {
+        match self {
+            Self::Json(format, _) => *format,
+            Self::JsonArray(_) => Format::JsonArray,
+            #[cfg(feature = "gzip")]
+            Self::Gzip(_) => Format::Gzip,
+            #[cfg(feature = "rmp-serde")]
+            Self::MessagePack(_) => Format::MessagePack,
+            #[cfg(feature = "ciborium")]
+            Self::Cbor(_) => Format::Cbor,
+        }
+    }
+}
+
+impl<T> Decoder<T> {
+    /// Create a new [`Decoder`].
+    pub const fn new() -> Self {
+        Self {
+            detected: None,
+            limits: Limits::NONE,
+            #[cfg(feature = "gzip")]
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+
+    /// Limit the size of a single JSON value, or a single element of
+    /// the JSON array, in bytes (see
+    /// [`crate::decoder::serde_json::Decoder::with_max_size`]).
+    pub const fn with_max_size(mut self, max_size: usize) -> Self {
+        self.limits.max_size = Some(max_size);
+        self
+    }
+
+    /// Limit the nesting depth of a single JSON value, or a single element of
+    /// the JSON array (see
+    /// [`crate::decoder::serde_json::Decoder::with_max_depth`]).
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.limits.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Limit the amount of the decompressed data pending decoding, 16 MiB by
+    /// default.
+    ///
+    /// The data is only decompressed when the value being decoded is
+    /// incomplete, in small pieces, so the limit effectively applies to
+    /// the size of a single value.
+    /// Exceeding the limit is reported as [`Error::DecompressedLimitExceeded`].
+    #[cfg(feature = "gzip")]
+    pub const fn with_max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
+
+    /// Create a new [`Decoder`] with the same settings.
+    #[cfg(feature = "gzip")]
+    const fn with_same_settings(&self) -> Self {
+        Self {
+            detected: None,
+            limits: self.limits,
+            max_decompressed_size: self.max_decompressed_size,
+        }
+    }
+
+    /// The detected format, if it has been detected already.
+    pub fn format(&self) -> Option<Format> {
+        self.detected.as_ref().map(Detected::format)
+    }
+
+    /// The detected format of the decompressed data, if the stream is
+    /// compressed and the format has been detected already.
+    pub fn decompressed_format(&self) -> Option<Format> {
+        match self.detected {
+            #[cfg(feature = "gzip")]
+            Some(Detected::Gzip(ref gunzip)) => gunzip.inner.format(),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Default for Decoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Decoder<T>
+where
+    T: for<'de> serde::de::Deserialize<'de>,
+{
+    /// Detect the format, if not detected yet, and decode the value.
+    fn decode_impl<Buffer>(
+        &mut self,
+        input: &mut Buffer,
+        eof: bool,
+    ) -> Result<T, crate::DecodeError<Error>>
+    where
+        Buffer: crate::Buffer,
+    {
+        let detected = match self.detected {
+            Some(ref mut detected) => detected,
+            None => {
+                let Some(format) = detect(input.view(), eof).map_err(crate::DecodeError::Other)?
+                else {
+                    return Err(crate::DecodeError::NeedMoreData);
+                };
+                let detected = Detected::new(format, self).map_err(crate::DecodeError::Other)?;
+                self.detected.insert(detected)
+            }
+        };
+
+        match detected {
+            Detected::Json(_, decoder) => {
+                let result = decode_with(decoder, input, eof, Error::Json);
+                if eof && matches!(result, Err(crate::DecodeError::NeedMoreData)) {
+                    // Drop the trailing whitespace, i.e. the last newline.
+                    let len = input.len();
+                    if input.view().iter().all(u8::is_ascii_whitespace) {
+                        input.advance(len);
+                        crate::Decoder::<Buffer>::reset(decoder);
+                    }
+                }
+                result
+            }
+            Detected::JsonArray(decoder) => decode_with(decoder, input, eof, Error::Json),
+            #[cfg(feature = "gzip")]
+            Detected::Gzip(gunzip) => gunzip.decode(input, eof),
+            #[cfg(feature = "rmp-serde")]
+            Detected::MessagePack(decoder) => decode_with(decoder, input, eof, Error::MessagePack),
+            #[cfg(feature = "ciborium")]
+            Detected::Cbor(decoder) => decode_with(decoder, input, eof, Error::Cbor),
+        }
+    }
+}
+
+impl<T, Buffer> crate::Decoder<Buffer> for Decoder<T>
+where
+    T: for<'de> serde::de::Deserialize<'de>,
+    Buffer: crate::Buffer,
+{
+    type Value = T;
+    type Error = Error;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        self.decode_impl(input, false)
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        self.decode_impl(input, true)
+    }
+
+    fn reset(&mut self) {
+        match self.detected {
+            None => {}
+            Some(Detected::Json(_, ref mut decoder)) => crate::Decoder::<Buffer>::reset(decoder),
+            Some(Detected::JsonArray(ref mut decoder)) => {
+                crate::Decoder::<Buffer>::reset(decoder);
+            }
+            #[cfg(feature = "gzip")]
+            Some(Detected::Gzip(ref mut gunzip)) => gunzip.abandon(),
+            #[cfg(feature = "rmp-serde")]
+            Some(Detected::MessagePack(ref mut decoder)) => {
+                crate::Decoder::<Buffer>::reset(decoder);
+            }
+            #[cfg(feature = "ciborium")]
+            Some(Detected::Cbor(ref mut decoder)) => crate::Decoder::<Buffer>::reset(decoder),
+        }
+    }
+}
+
+/// Decode the value with the given decoder, wrapping the error.
+fn decode_with<D, Buffer, E>(
+    decoder: &mut D,
+    input: &mut Buffer,
+    eof: bool,
+    wrap: impl FnOnce(D::Error) -> E,
+) -> Result<D::Value, crate::DecodeError<E>>
+where
+    D: crate::Decoder<Buffer>,
+    Buffer: crate::Buffer,
+{
+    let result = if eof {
+        decoder.decode_eof(input)
+    } else {
+        decoder.decode(input)
+    };
+    result.map_err(|err| match err {
+        crate::DecodeError::NeedMoreData => crate::DecodeError::NeedMoreData,
+        crate::DecodeError::SkipData(bytes) => crate::DecodeError::SkipData(bytes),
+        crate::DecodeError::Other(err) => crate::DecodeError::Other(wrap(err)),
+        crate::DecodeError::Rejected(err) => crate::DecodeError::Rejected(wrap(err)),
+    })
+}
+
+/// The decoder of the gzip-wrapped data.
+///
+/// The compressed data is decompressed piece by piece, only as long as
+/// the decoder of the decompressed data needs more data, and
+/// the decompressed data is kept in the own buffer.
+#[cfg(feature = "gzip")]
+#[derive(Debug)]
+struct Gunzip<T> {
+    /// The decompressor.
+    transform: crate::transform::decompress::Gzip,
+    /// The decompressed data.
+    buffer: Vec<u8>,
+    /// The limit on the amount of the decompressed data.
+    max_bytes: usize,
+    /// The decoder of the decompressed data.
+    inner: Decoder<T>,
+    /// Whether the decompressor has been finished.
+    finished: bool,
+    /// Whether the decoding has been abandoned after an error.
+    abandoned: bool,
+}
+
+#[cfg(feature = "gzip")]
+impl<T> Gunzip<T> {
+    /// Create a new [`Gunzip`], with the settings of the given [`Decoder`].
+    fn new(settings: &Decoder<T>) -> Self {
+        Self {
+            transform: crate::transform::decompress::Gzip::new(),
+            buffer: Vec::new(),
+            max_bytes: settings.max_decompressed_size,
+            inner: settings.with_same_settings(),
+            finished: false,
+            abandoned: false,
+        }
+    }
+
+    /// Abandon the decoding, as there is no way to resume decompressing
+    /// the stream from an arbitrary point.
+    fn abandon(&mut self) {
+        if !self.abandoned {
+            tracing::warn!("unable to recover within the gzip stream, skipping the rest of it");
+        }
+        self.abandoned = true;
+        self.buffer = Vec::new();
+        self.inner = self.inner.with_same_settings();
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<T> Gunzip<T>
+where
+    T: for<'de> serde::de::Deserialize<'de>,
+{
+    /// Decompress the input as needed and decode the value.
+    fn decode<Buffer>(
+        &mut self,
+        input: &mut Buffer,
+        eof: bool,
+    ) -> Result<T, crate::DecodeError<Error>>
+    where
+        Buffer: crate::Buffer,
+    {
+        if self.abandoned {
+            return match input.len() {
+                0 => Err(crate::DecodeError::NeedMoreData),
+                len => Err(crate::DecodeError::SkipData(len)),
+            };
+        }
+
+        loop {
+            let inner_eof = eof && input.is_empty();
+            if inner_eof && !self.finished {
+                self.finished = true;
+                crate::transform::Transform::finish(&mut self.transform, &mut self.buffer)
+                    .map_err(|err| crate::DecodeError::Other(Error::Gzip(err)))?;
+            }
+
+            let result = self.inner.decode_impl(&mut self.buffer, inner_eof);
+            return match result {
+                // The skips apply to the decompressed data.
+                Err(crate::DecodeError::SkipData(bytes)) => {
+                    self.buffer.advance(bytes);
+                    continue;
+                }
+                Err(crate::DecodeError::NeedMoreData) if !input.is_empty() => {
+                    let pending = self.buffer.len();
+                    if pending > self.max_bytes {
+                        return Err(crate::DecodeError::Other(
+                            Error::DecompressedLimitExceeded {
+                                pending,
+                                max_bytes: self.max_bytes,
+                            },
+                        ));
+                    }
+                    let len = input.len().min(DECOMPRESS_CHUNK_SIZE);
+                    crate::transform::Transform::transform(
+                        &mut self.transform,
+                        input.view().get(..len).unwrap_or_default(),
+                        &mut self.buffer,
+                    )
+                    .map_err(|err| crate::DecodeError::Other(Error::Gzip(err)))?;
+                    input.advance(len);
+                    continue;
+                }
+                Err(crate::DecodeError::NeedMoreData) if inner_eof && !self.buffer.is_empty() => {
+                    let bytes = self.buffer.len();
+                    self.buffer.clear();
+                    Err(crate::DecodeError::Other(Error::TrailingData { bytes }))
+                }
+                result => result,
+            };
+        }
+    }
+}
+
+/// An error that can occur while decoding the stream of the detected format.
+#[derive(Debug)]
+pub enum Error {
+    /// The format is not recognized.
+    Unrecognized,
+    /// The format is recognized, but the support for it is not enabled.
+    Unsupported(Format),
+    /// Unable to decode the JSON-based format.
+    Json(crate::decoder::serde_json::Error),
+    /// Unable to decode the msgpack value.
+    #[cfg(feature = "rmp-serde")]
+    MessagePack(rmp_serde::decode::Error),
+    /// Unable to decode the CBOR value.
+    #[cfg(feature = "ciborium")]
+    Cbor(ciborium::de::Error<std::io::Error>),
+    /// Unable to decompress the data.
+    #[cfg(feature = "gzip")]
+    Gzip(std::io::Error),
+    /// The decompressed data does not fit within the limit (see
+    /// [`Decoder::with_max_decompressed_size`]).
+    #[cfg(feature = "gzip")]
+    DecompressedLimitExceeded {
+        /// The amount of the decompressed bytes pending decoding.
+        pending: usize,
+        /// The configured limit.
+        max_bytes: usize,
+    },
+    /// The decompressed data ended with an incomplete value, which has been
+    /// dropped.
+    TrailingData {
+        /// The amount of bytes dropped.
+        bytes: usize,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unrecognized => f.write_str("unrecognized stream format"),
+            Self::Unsupported(format) => write!(f, "unsupported stream format: {format}"),
+            Self::Json(err) => write!(f, "{}: {err}", Format::Json),
+            #[cfg(feature = "rmp-serde")]
+            Self::MessagePack(err) => write!(f, "{}: {err}", Format::MessagePack),
+            #[cfg(feature = "ciborium")]
+            Self::Cbor(err) => write!(f, "{}: {err}", Format::Cbor),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(err) => write!(f, "{}: {err}", Format::Gzip),
+            #[cfg(feature = "gzip")]
+            Self::DecompressedLimitExceeded { pending, max_bytes } => write!(
+                f,
+                "{}: decompressed data limit of {max_bytes} bytes exceeded: {pending} bytes pending",
+                Format::Gzip
+            ),
+            Self::TrailingData { bytes } => {
+                write!(f, "{bytes} bytes of trailing decompressed data")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Json(err) => Some(err),
+            #[cfg(feature = "rmp-serde")]
+            Self::MessagePack(err) => Some(err),
+            #[cfg(feature = "ciborium")]
+            Self::Cbor(err) => Some(err),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(err) => Some(err),
+            #[cfg(feature = "gzip")]
+            Self::DecompressedLimitExceeded { .. } => None,
+            Self::Unrecognized | Self::Unsupported(_) | Self::TrailingData { .. } => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "rmp-serde", feature = "ciborium", feature = "gzip"))]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn objects() -> [TO; 2] {
+        [
+            TestObject {
+                field: "val0".into(),
+            },
+            TestObject {
+                field: "val1".into(),
+            },
+        ]
+    }
+
+    /// Decode the data chunk by chunk, returning the values and
+    /// the detected format.
+    fn decode(data: &[u8], chunk_size: usize) -> (Vec<TO>, Option<Format>) {
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new());
+
+        let mut values = Vec::new();
+        for chunk in data.chunks(chunk_size) {
+            values.extend(dec.process_next_chunk(chunk).map(Result::unwrap));
+        }
+        values.extend(dec.process_eof().map(Result::unwrap));
+
+        let format = dec.decoder.format();
+        assert!(dec.finish().is_ok());
+        (values, format)
+    }
+
+    fn check(data: &[u8], format: Format) {
+        for chunk_size in [1, 2, 5, data.len()] {
+            assert_eq!(decode(data, chunk_size), (objects().into(), Some(format)));
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        check(br#" {"field": "val0"} {"field": "val1"}"#, Format::Json);
+        check(
+            b"{\n  \"field\": \"val0\"\n}\n{\n  \"field\": \"val1\"\n}\n",
+            Format::Json,
+        );
+        check(
+            b"{\"field\": \"val0\"}\n{\"field\": \"val1\"}\n",
+            Format::Ndjson,
+        );
+        check(
+            br#"[{"field": "val0"}, {"field": "val1"}]"#,
+            Format::JsonArray,
+        );
+    }
+
+    #[test]
+    fn test_binary() {
+        let mut data = Vec::new();
+        for object in objects() {
+            data.extend(rmp_serde::to_vec_named(&object).unwrap());
+        }
+        check(&data, Format::MessagePack);
+
+        let mut data = Vec::new();
+        for object in objects() {
+            ciborium::ser::into_writer(&object, &mut data).unwrap();
+        }
+        check(&data, Format::Cbor);
+    }
+
+    #[test]
+    fn test_gzip() {
+        let data = gzip(b"{\"field\": \"val0\"}\n{\"field\": \"val1\"}\n");
+
+        check(&data, Format::Gzip);
+
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new());
+        assert!(dec.process_next_chunk(&data).next().is_some());
+        assert_eq!(dec.decoder.decompressed_format(), Some(Format::Ndjson));
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_gzip_limits() {
+        let data = gzip(&b"{\"field\": \"val0\"}\n".repeat(100_000));
+        let mut dec = crate::State::new(
+            Decoder::<TO>::new().with_max_decompressed_size(64 * 1024),
+            Vec::new(),
+        );
+        // Only decompressed as needed, so the whole stream fits.
+        assert_eq!(dec.process_next_chunk(&data).count(), 100_000);
+        assert!(dec.process_eof().next().is_none());
+        assert!(dec.finish().is_ok());
+
+        // Not compressible, unlike the repeated values.
+        let mut seed = 1_u32;
+        let long_value: String = std::iter::repeat_with(|| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            char::from(b'a' + u8::try_from(seed >> 16 & 0xff).unwrap() % 26)
+        })
+        .take(200_000)
+        .collect();
+        let data = gzip(format!("{{\"field\": \"{long_value}\"}}\n").as_bytes());
+        let mut dec = crate::State::new(
+            Decoder::<TO>::new().with_max_decompressed_size(64 * 1024),
+            Vec::new(),
+        );
+        assert!(matches!(
+            dec.process_next_chunk(&data).next().unwrap().unwrap_err(),
+            Error::DecompressedLimitExceeded {
+                max_bytes: 65_536,
+                ..
+            }
+        ));
+
+        let mut dec = crate::State::new(Decoder::<TO>::new().with_max_size(16), Vec::new());
+        assert!(matches!(
+            dec.process_next_chunk(&gzip(b"{\"field\": \"val0\"}\n"))
+                .next()
+                .unwrap()
+                .unwrap_err(),
+            Error::Json(crate::decoder::serde_json::Error::TooLarge { max_size: 16 })
+        ));
+    }
+
+    #[test]
+    fn test_gzip_recovery() {
+        let data = gzip(b"{\"field\": \"val0\"}\n{\"field\": 1}\n{\"field\": \"val2\"}\n");
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new())
+            .with_recovery(crate::Recovery::SkipToNewline);
+
+        let mut results = Vec::new();
+        for chunk in data.chunks(16) {
+            results.extend(dec.process_next_chunk(chunk));
+        }
+        results.extend(dec.process_eof());
+        // The rest of the stream is skipped after the error.
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().field, "val0");
+        assert!(matches!(results[1], Err(Error::Json(_))));
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_need_more_data() {
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new());
+
+        for chunk in [&b"  "[..], b"{\"field\"", b": \"val0\"}"] {
+            assert!(dec.process_next_chunk(chunk).next().is_none());
+            assert_eq!(dec.decoder.format(), None);
+        }
+
+        {
+            let mut stream = dec.process_next_chunk(b"\n");
+            assert_eq!(stream.next().unwrap().unwrap().field, "val0");
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(dec.decoder.format(), Some(Format::Ndjson));
+
+        assert!(dec.process_eof().next().is_none());
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_unrecognized() {
+        let mut dec = crate::State::new(Decoder::<TO>::new(), Vec::new());
+
+        {
+            let mut stream = dec.process_next_chunk(b"<html>");
+            assert!(matches!(
+                stream.next().unwrap().unwrap_err(),
+                Error::Unrecognized
+            ));
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(dec.decoder.format(), None);
+
+        assert_eq!(dec.finish().unwrap_err(), b"<html>");
+    }
+}
@@ -0,0 +1,25 @@
+//! The helpers shared by the decoder tests.
+//!
+//! Not every helper is used with every set of the features enabled.
+#![allow(dead_code)]
+
+/// Decode all the data, resuming the decoding after the errors.
+pub(super) fn decode_all<Decoder, Buffer>(
+    state: &mut crate::State<Decoder, Buffer>,
+    data: &[u8],
+) -> Vec<Result<Decoder::Value, Decoder::Error>>
+where
+    Decoder: crate::Decoder<Buffer>,
+    Buffer: crate::Buffer,
+{
+    let mut results = Vec::new();
+    let mut chunk = data;
+    loop {
+        let before = results.len();
+        results.extend(state.process_next_chunk(chunk));
+        chunk = b"";
+        if results.len() == before {
+            return results;
+        }
+    }
+}
@@ -3,6 +3,14 @@
 use crate::Buffer;
 
 /// The decoder that wraps any [`tokio_util::codec::Decoder`].
+///
+/// At the end of the stream, the decoding is delegated to
+/// [`tokio_util::codec::Decoder::decode_eof`], which, unless overridden by
+/// the inner decoder, fails with the "bytes remaining on stream" I/O error
+/// (converted to the inner decoder error) if there is an incomplete frame
+/// left in the buffer.
+/// So the leftover data is reported as a [`crate::DecodeError::Other`]
+/// rather than being left in the buffer for [`crate::State::finish`].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Decoder<T> {
     /// The inner decoder type.
@@ -16,6 +24,21 @@ impl<T> Decoder<T> {
     }
 }
 
+/// Lend the contents of the [`Vec`] as [`bytes::BytesMut`] to a given fn.
+///
+/// The conversions both ways reuse the allocation, so, unlike copying into
+/// a fresh [`bytes::BytesMut`] on every call, this does not cost more than
+/// the inner decoder consuming the data from the [`Vec`] directly.
+/// Any data consumed by the inner decoder is consumed from the [`Vec`] too,
+/// so the stateful decoders (i.e. the ones consuming the frame header
+/// before the whole frame is available) work correctly.
+fn lend_bytes_mut<R>(input: &mut Vec<u8>, f: impl FnOnce(&mut bytes::BytesMut) -> R) -> R {
+    let mut buf = bytes::BytesMut::from(bytes::Bytes::from(std::mem::take(input)));
+    let result = f(&mut buf);
+    *input = buf.into();
+    result
+}
+
 impl<T> crate::Decoder<Vec<u8>> for Decoder<T>
 where
     T: tokio_util::codec::Decoder,
@@ -23,21 +46,18 @@ where
     type Value = <T as tokio_util::codec::Decoder>::Item;
     type Error = <T as tokio_util::codec::Decoder>::Error;
 
-    #[allow(clippy::arithmetic_side_effects)]
     fn decode(
         &mut self,
         input: &mut Vec<u8>,
     ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
-        let buf = input.view();
-        let mut buf_bytes = bytes::BytesMut::from(buf);
-        match tokio_util::codec::Decoder::decode(&mut self.inner, &mut buf_bytes) {
-            Ok(None) => Err(crate::DecodeError::NeedMoreData),
-            Ok(Some(value)) => {
-                input.advance(buf.len() - buf_bytes.len());
-                Ok(value)
-            }
-            Err(err) => Err(crate::DecodeError::Other(err)),
-        }
+        lend_bytes_mut(input, |buf| crate::Decoder::decode(self, buf))
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Vec<u8>,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        lend_bytes_mut(input, |buf| crate::Decoder::decode_eof(self, buf))
     }
 }
 
@@ -59,4 +79,391 @@ where
             Err(err) => Err(crate::DecodeError::Other(err)),
         }
     }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut bytes::BytesMut,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        match tokio_util::codec::Decoder::decode_eof(&mut self.inner, input) {
+            Ok(None) => Err(crate::DecodeError::NeedMoreData),
+            Ok(Some(value)) => Ok(value),
+            Err(err) => Err(crate::DecodeError::Other(err)),
+        }
+    }
+}
+
+/// The [`tokio_util::codec::Decoder`] that wraps any [`crate::Decoder`],
+/// i.e. the reverse of [`Decoder`].
+///
+/// This allows using the decoders from this crate with
+/// [`tokio_util::codec::FramedRead`] and the like.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Codec<T> {
+    /// The inner decoder type.
+    pub inner: T,
+}
+
+impl<T> Codec<T> {
+    /// Create a new [`Codec`] with a given [`crate::Decoder`].
+    pub const fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+/// The error of the [`Codec`].
+#[derive(Debug)]
+pub enum CodecError<T> {
+    /// The I/O error, as required by the [`tokio_util::codec::Decoder`].
+    Io(std::io::Error),
+    /// The inner decoder error.
+    Decode(T),
+}
+
+impl<T> std::fmt::Display for CodecError<T>
+where
+    T: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Decode(err) => write!(f, "decode error: {err}"),
+        }
+    }
+}
+
+impl<T> std::error::Error for CodecError<T>
+where
+    T: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Decode(err) => Some(err),
+        }
+    }
+}
+
+impl<T> From<std::io::Error> for CodecError<T> {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl<T> Codec<T>
+where
+    T: crate::Decoder<bytes::BytesMut>,
+{
+    /// Decode the value, skipping the data as requested by the inner
+    /// decoder.
+    fn decode_skipping(
+        &mut self,
+        src: &mut bytes::BytesMut,
+        eof: bool,
+    ) -> Result<Option<T::Value>, CodecError<T::Error>> {
+        loop {
+            let result = if eof {
+                self.inner.decode_eof(src)
+            } else {
+                self.inner.decode(src)
+            };
+            return match result {
+                Ok(value) => Ok(Some(value)),
+                Err(crate::DecodeError::NeedMoreData) => Ok(None),
+                Err(crate::DecodeError::SkipData(bytes_to_skip)) => {
+                    src.advance(bytes_to_skip);
+                    continue; // skip return
+                }
+                Err(crate::DecodeError::Other(err) | crate::DecodeError::Rejected(err)) => {
+                    Err(CodecError::Decode(err))
+                }
+            };
+        }
+    }
+}
+
+impl<T> tokio_util::codec::Decoder for Codec<T>
+where
+    T: crate::Decoder<bytes::BytesMut>,
+{
+    type Item = T::Value;
+    type Error = CodecError<T::Error>;
+
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_skipping(src, false)
+    }
+
+    fn decode_eof(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode_skipping(src, true)? {
+            Some(value) => Ok(Some(value)),
+            None if src.is_empty() => Ok(None),
+            // Same as the default `decode_eof` of the `tokio_util`.
+            None => Err(std::io::Error::other("bytes remaining on stream").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_state<Buffer: Default>() -> crate::State<Decoder<tokio_util::codec::LinesCodec>, Buffer>
+    {
+        crate::State::new(
+            Decoder::new(tokio_util::codec::LinesCodec::new()),
+            Buffer::default(),
+        )
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut dec = make_state::<Vec<u8>>();
+
+        {
+            let stream = dec.process_next_chunk(b"line0\nline1\nli");
+            let lines: Vec<String> = stream.try_collect().unwrap();
+            assert_eq!(lines, ["line0", "line1"]);
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), b"li");
+    }
+
+    #[test]
+    fn test_eof_flushes_last_line() {
+        let mut dec = make_state::<Vec<u8>>();
+
+        {
+            let stream = dec.process_next_chunk(b"line0\nline1");
+            let lines: Vec<String> = stream.try_collect().unwrap();
+            assert_eq!(lines, ["line0"]);
+        }
+
+        {
+            let stream = dec.process_eof();
+            let lines: Vec<String> = stream.try_collect().unwrap();
+            assert_eq!(lines, ["line1"]);
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_eof_flushes_last_line_bytes_mut() {
+        let mut dec = make_state::<bytes::BytesMut>();
+
+        {
+            let stream = dec.process_next_chunk(b"line0\nline1");
+            let lines: Vec<String> = stream.try_collect().unwrap();
+            assert_eq!(lines, ["line0"]);
+        }
+
+        {
+            let stream = dec.process_eof();
+            let lines: Vec<String> = stream.try_collect().unwrap();
+            assert_eq!(lines, ["line1"]);
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_eof_on_empty() {
+        let mut dec = make_state::<Vec<u8>>();
+
+        {
+            let mut stream = dec.process_eof();
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_eof_with_incomplete_frame() {
+        let mut dec = crate::State::new(
+            Decoder::new(tokio_util::codec::LengthDelimitedCodec::new()),
+            Vec::new(),
+        );
+
+        assert!(dec.process_next_chunk(b"\0\0\0\x04va").next().is_none());
+
+        let err = dec.process_eof().next().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "bytes remaining on stream");
+    }
+
+    #[test]
+    fn test_large_frame_is_linear() {
+        const SIZE: usize = 1024 * 1024;
+        const CHUNK_SIZE: usize = 1024;
+
+        let mut dec = crate::State::new(
+            Decoder::new(
+                tokio_util::codec::LengthDelimitedCodec::builder()
+                    .max_frame_length(usize::MAX)
+                    .new_codec(),
+            ),
+            Vec::new(),
+        );
+        let header = u32::try_from(SIZE).unwrap().to_be_bytes();
+        let chunk = [0xAB; CHUNK_SIZE];
+
+        assert!(dec.process_next_chunk(&header).next().is_none());
+        let mut frames = Vec::new();
+        let mut storage = (dec.buffer.as_ptr(), dec.buffer.capacity());
+        let mut reallocations = 0;
+        for _ in 0..SIZE / CHUNK_SIZE {
+            frames.extend(dec.process_next_chunk(&chunk).map(Result::unwrap));
+            let next = (dec.buffer.as_ptr(), dec.buffer.capacity());
+            if next != storage && !dec.buffer.is_empty() {
+                reallocations += 1;
+            }
+            storage = next;
+        }
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].len(), SIZE);
+        assert!(dec.finish().is_ok());
+
+        // Copying the buffer on every call would reallocate it for every
+        // chunk, rather than only as it grows.
+        assert!(
+            reallocations <= 2 * (SIZE / CHUNK_SIZE).ilog2(),
+            "{reallocations} reallocations for {} chunks",
+            SIZE / CHUNK_SIZE
+        );
+    }
+
+    #[test]
+    fn test_stateful_codec() {
+        let mut dec = crate::State::new(
+            Decoder::new(tokio_util::codec::LengthDelimitedCodec::new()),
+            Vec::new(),
+        );
+
+        // The codec consumes the header before the whole frame is available.
+        assert!(dec.process_next_chunk(b"\0\0\0\x04va").next().is_none());
+        assert_eq!(dec.offset, 4);
+
+        let frames: Vec<_> = dec
+            .process_next_chunk(b"l0\0\0\0\x00")
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(frames, [&b"val0"[..], b""]);
+        assert!(dec.finish().is_ok());
+    }
+
+    /// The decoder of the single digits that skips everything else.
+    struct Digits;
+
+    impl crate::Decoder<bytes::BytesMut> for Digits {
+        type Value = u8;
+        type Error = std::convert::Infallible;
+
+        fn decode(
+            &mut self,
+            input: &mut bytes::BytesMut,
+        ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+            match input.iter().position(u8::is_ascii_digit) {
+                None if input.is_empty() => Err(crate::DecodeError::NeedMoreData),
+                None => Err(crate::DecodeError::SkipData(input.len())),
+                Some(0) => {
+                    let digit = input[0];
+                    input.advance(1);
+                    Ok(digit)
+                }
+                Some(non_digits) => Err(crate::DecodeError::SkipData(non_digits)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_codec_skip_data() {
+        let mut codec = Codec::new(Digits);
+        let mut src = bytes::BytesMut::from(&b"a1bc2def"[..]);
+
+        let mut decoded = Vec::new();
+        while let Some(digit) = tokio_util::codec::Decoder::decode(&mut codec, &mut src).unwrap() {
+            decoded.push(digit);
+        }
+        assert_eq!(decoded, b"12");
+        assert!(src.is_empty());
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_codec_json() {
+        use tokio_util::codec::Decoder as _;
+
+        let mut codec = Codec::new(crate::decoder::serde_json::Decoder::<Vec<u32>>::new());
+        let mut src = bytes::BytesMut::new();
+
+        src.extend_from_slice(b"[1, 2]\n[3");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(vec![1, 2]));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+        src.extend_from_slice(b"]\n[4]");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(vec![3]));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(vec![4]));
+        assert_eq!(codec.decode_eof(&mut src).unwrap(), None);
+
+        src.extend_from_slice(b"[\"5\"]");
+        assert!(matches!(
+            codec.decode(&mut src).unwrap_err(),
+            CodecError::Decode(_)
+        ));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_codec_eof() {
+        use tokio_util::codec::Decoder as _;
+
+        let mut codec = Codec::new(Decoder::new(tokio_util::codec::LinesCodec::new()));
+        let mut src = bytes::BytesMut::from(&b"line0\nline1"[..]);
+
+        assert_eq!(codec.decode(&mut src).unwrap().as_deref(), Some("line0"));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert_eq!(
+            codec.decode_eof(&mut src).unwrap().as_deref(),
+            Some("line1")
+        );
+        assert_eq!(codec.decode_eof(&mut src).unwrap(), None);
+
+        let mut codec = Codec::new(crate::decoder::serde_json::Decoder::<Vec<u32>>::new());
+        let mut src = bytes::BytesMut::from(&b"[1"[..]);
+        assert!(matches!(
+            codec.decode_eof(&mut src).unwrap_err(),
+            CodecError::Io(_)
+        ));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_codec_framed_read() {
+        use futures_core::Stream;
+
+        let input: &[u8] = b"1\n{\"a\": 2}\n[3]";
+        let mut framed = tokio_util::codec::FramedRead::with_capacity(
+            input,
+            Codec::new(crate::decoder::serde_json::Decoder::<serde_json::Value>::new()),
+            4,
+        );
+
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        let mut decoded = Vec::new();
+        loop {
+            match std::pin::Pin::new(&mut framed).poll_next(&mut cx) {
+                std::task::Poll::Ready(Some(value)) => decoded.push(value.unwrap()),
+                std::task::Poll::Ready(None) => break,
+                std::task::Poll::Pending => unreachable!("the input is always ready"),
+            }
+        }
+        assert_eq!(
+            decoded,
+            [
+                serde_json::json!(1),
+                serde_json::json!({"a": 2}),
+                serde_json::json!([3]),
+            ]
+        );
+    }
 }
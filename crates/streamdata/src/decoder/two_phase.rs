@@ -0,0 +1,269 @@
+//! Two-phase decoding, where the stream switches the format midway, i.e.
+//! the text header followed by the body.
+
+/// Either of the two phases.
+///
+/// Used for the decoder state, as well as for the values and the errors
+/// of the [`Decoder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Phase<First, Second> {
+    /// The first phase.
+    First(First),
+    /// The second phase.
+    Second(Second),
+}
+
+impl<First, Second> std::fmt::Display for Phase<First, Second>
+where
+    First: std::fmt::Display,
+    Second: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::First(value) => write!(f, "first phase: {value}"),
+            Self::Second(value) => write!(f, "second phase: {value}"),
+        }
+    }
+}
+
+impl<First, Second> std::error::Error for Phase<First, Second>
+where
+    First: std::error::Error + 'static,
+    Second: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::First(err) => Some(err),
+            Self::Second(err) => Some(err),
+        }
+    }
+}
+
+/// The decoder that decodes the values with the first decoder until
+/// the switch function produces the second decoder, and then continues
+/// decoding the rest of the stream with the second decoder.
+///
+/// The switch function is called with each value decoded in the first
+/// phase; the value that triggers the switch is still yielded as a first
+/// phase value.
+/// Since the second decoder is produced from the first phase values, it can
+/// be configured by them, i.e. by the header.
+///
+/// Use [`crate::State::switch_decoder`] instead when the switch is driven
+/// from outside of the decoding.
+pub struct Decoder<First, Second, Switch> {
+    /// The current phase.
+    pub phase: Phase<First, Second>,
+    /// The switch function.
+    switch: Switch,
+}
+
+impl<First, Second, Switch> Decoder<First, Second, Switch> {
+    /// Create a new [`Decoder`] starting with the given first phase decoder.
+    pub const fn new(first: First, switch: Switch) -> Self {
+        Self {
+            phase: Phase::First(first),
+            switch,
+        }
+    }
+}
+
+impl<First, Second, Switch> std::fmt::Debug for Decoder<First, Second, Switch>
+where
+    First: std::fmt::Debug,
+    Second: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Decoder")
+            .field("phase", &self.phase)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<First, Second, Switch> Clone for Decoder<First, Second, Switch>
+where
+    First: Clone,
+    Second: Clone,
+    Switch: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            phase: self.phase.clone(),
+            switch: self.switch.clone(),
+        }
+    }
+}
+
+impl<First, Second, Switch, Buffer> crate::Decoder<Buffer> for Decoder<First, Second, Switch>
+where
+    Buffer: crate::Buffer,
+    First: crate::Decoder<Buffer>,
+    Second: crate::Decoder<Buffer>,
+    Switch: FnMut(&First::Value) -> Option<Second>,
+{
+    type Value = Phase<First::Value, Second::Value>;
+    type Error = Phase<First::Error, Second::Error>;
+
+    fn decode(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        match self.phase {
+            Phase::First(ref mut first) => {
+                let value = first
+                    .decode(input)
+                    .map_err(|err| phase_error(err, Phase::First))?;
+                Ok(self.after_first(value))
+            }
+            Phase::Second(ref mut second) => second
+                .decode(input)
+                .map(Phase::Second)
+                .map_err(|err| phase_error(err, Phase::Second)),
+        }
+    }
+
+    fn decode_eof(
+        &mut self,
+        input: &mut Buffer,
+    ) -> Result<Self::Value, crate::DecodeError<Self::Error>> {
+        match self.phase {
+            Phase::First(ref mut first) => {
+                let value = first
+                    .decode_eof(input)
+                    .map_err(|err| phase_error(err, Phase::First))?;
+                Ok(self.after_first(value))
+            }
+            Phase::Second(ref mut second) => second
+                .decode_eof(input)
+                .map(Phase::Second)
+                .map_err(|err| phase_error(err, Phase::Second)),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self.phase {
+            Phase::First(ref mut first) => first.reset(),
+            Phase::Second(ref mut second) => second.reset(),
+        }
+    }
+}
+
+impl<First, Second, Switch> Decoder<First, Second, Switch> {
+    /// Switch to the second phase if the given first phase value says so.
+    fn after_first<FirstValue, SecondValue>(
+        &mut self,
+        value: FirstValue,
+    ) -> Phase<FirstValue, SecondValue>
+    where
+        Switch: FnMut(&FirstValue) -> Option<Second>,
+    {
+        if let Some(second) = (self.switch)(&value) {
+            self.phase = Phase::Second(second);
+        }
+        Phase::First(value)
+    }
+}
+
+/// Wrap the decoding error of either phase.
+fn phase_error<T, E>(
+    err: crate::DecodeError<T>,
+    wrap: impl FnOnce(T) -> E,
+) -> crate::DecodeError<E> {
+    match err {
+        crate::DecodeError::NeedMoreData => crate::DecodeError::NeedMoreData,
+        crate::DecodeError::SkipData(bytes) => crate::DecodeError::SkipData(bytes),
+        crate::DecodeError::Other(err) => crate::DecodeError::Other(wrap(err)),
+        crate::DecodeError::Rejected(err) => crate::DecodeError::Rejected(wrap(err)),
+    }
+}
+
+#[cfg(all(test, feature = "serde_json", feature = "tokio-util"))]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    type Lines = crate::decoder::tokio_util::Decoder<tokio_util::codec::LinesCodec>;
+
+    fn lines() -> Lines {
+        crate::decoder::tokio_util::Decoder::new(tokio_util::codec::LinesCodec::new())
+    }
+
+    const DATA: &str = concat!(
+        "Content-Type: application/json\n",
+        "\n",
+        r#"{"field": "val0"}{"field": "val1"}"#,
+    );
+
+    #[test]
+    fn test_switch_decoder() {
+        let mut dec = crate::State::new(lines(), Vec::new());
+
+        let mut headers = Vec::new();
+        for line in dec.process_next_chunk(&DATA.as_bytes()[..40]) {
+            let line = line.unwrap();
+            if line.is_empty() {
+                break;
+            }
+            headers.push(line);
+        }
+        assert_eq!(headers, ["Content-Type: application/json"]);
+        assert_eq!(dec.offset, 32);
+
+        let (_, mut dec) = dec.switch_decoder(crate::decoder::serde_json::Decoder::<TO>::new());
+        assert_eq!(dec.offset, 32);
+
+        {
+            let mut stream = dec.process_next_chunk(&DATA.as_bytes()[40..]);
+            assert_eq!(stream.next().unwrap().unwrap().field, "val0");
+            assert_eq!(stream.next().unwrap().unwrap().field, "val1");
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_two_phase() {
+        let mut dec = crate::State::new(
+            Decoder::new(lines(), |line: &String| {
+                line.is_empty()
+                    .then(crate::decoder::serde_json::Decoder::<TO>::new)
+            }),
+            Vec::new(),
+        );
+
+        let mut values = Vec::new();
+        for chunk in DATA.as_bytes().chunks(5) {
+            values.extend(dec.process_next_chunk(chunk).map(Result::unwrap));
+        }
+
+        let mut values = values.into_iter();
+        assert_eq!(
+            values.next(),
+            Some(Phase::First("Content-Type: application/json".into()))
+        );
+        assert_eq!(values.next(), Some(Phase::First(String::new())));
+        assert_eq!(
+            values.next(),
+            Some(Phase::Second(TestObject {
+                field: "val0".into()
+            }))
+        );
+        assert_eq!(
+            values.next(),
+            Some(Phase::Second(TestObject {
+                field: "val1".into()
+            }))
+        );
+        assert!(values.next().is_none());
+        assert!(matches!(dec.decoder.phase, Phase::Second(_)));
+
+        assert!(dec.finish().is_ok());
+    }
+}
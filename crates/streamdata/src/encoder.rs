@@ -0,0 +1,6 @@
+//! Encoder implementations.
+
+#[cfg(feature = "serde_json")]
+pub mod serde_json;
+#[cfg(feature = "tokio-util")]
+pub mod tokio_util;
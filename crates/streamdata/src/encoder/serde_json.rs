@@ -0,0 +1,160 @@
+//! [`serde_json`] integration.
+
+use std::marker::PhantomData;
+
+/// The encoder for [`serde_json`].
+///
+/// Produces the newline-delimited JSON, i.e. each value on its own line, as
+/// consumed by the [`crate::decoder::serde_json::Decoder`] and used by
+/// the Kubernetes watch streams.
+///
+/// Use the reference types (i.e. `Encoder<&MyType>`) to encode the values
+/// without giving up the ownership.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encoder<T> {
+    /// The marker for data type to encode.
+    pub data_type: PhantomData<T>,
+    /// The intermediate buffer, so that nothing is appended to the output if
+    /// the encoding fails.
+    scratch: Vec<u8>,
+}
+
+impl<T> Encoder<T> {
+    /// Create a new [`Encoder`].
+    pub const fn new() -> Self {
+        Self {
+            data_type: PhantomData,
+            scratch: Vec::new(),
+        }
+    }
+}
+
+impl<T> Default for Encoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, Buffer> crate::Encoder<Buffer> for Encoder<T>
+where
+    T: serde::Serialize,
+    Buffer: crate::Buffer,
+{
+    type Value = T;
+    type Error = serde_json::Error;
+
+    fn encode(&mut self, value: Self::Value, output: &mut Buffer) -> Result<(), Self::Error> {
+        self.scratch.clear();
+        serde_json::to_writer(&mut self.scratch, &value)?;
+        self.scratch.push(b'\n');
+        output.append(&self.scratch);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn make_to(field: &str) -> TO {
+        TO {
+            field: field.into(),
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut enc = crate::EncodingState::new(Encoder::<&TO>::new(), Vec::new());
+
+        let values = [make_to("val0"), make_to("val1")];
+        enc.encode_all(&values).unwrap();
+        assert_eq!(
+            enc.pending(),
+            b"{\"field\":\"val0\"}\n{\"field\":\"val1\"}\n"
+        );
+
+        enc.consume(17);
+        assert_eq!(enc.offset, 17);
+        assert_eq!(enc.pending(), b"{\"field\":\"val1\"}\n");
+
+        enc.finish().unwrap();
+        enc.consume(17);
+        assert!(enc.is_empty());
+    }
+
+    #[test]
+    fn test_error() {
+        let mut enc = crate::EncodingState::new(Encoder::new(), Vec::new());
+
+        let mut map = std::collections::HashMap::new();
+        map.insert(vec![1], "val0");
+        assert!(enc.encode(&map).is_err());
+        assert!(enc.is_empty());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let values = [make_to("val0"), make_to("val1"), make_to("\n\"")];
+
+        let mut enc = crate::EncodingState::new(Encoder::new(), Vec::new());
+        enc.encode_all(&values).unwrap();
+
+        let mut dec =
+            crate::State::new(crate::decoder::serde_json::Decoder::<TO>::new(), Vec::new());
+        let mut decoded = Vec::new();
+        for chunk in enc.pending().chunks(3) {
+            decoded.extend(dec.process_next_chunk(chunk).map(Result::unwrap));
+        }
+        assert_eq!(decoded, values);
+    }
+
+    #[cfg(feature = "k8s-openapi")]
+    #[test]
+    fn test_k8s_round_trip() {
+        use k8s_openapi::{
+            api::core::v1::Pod,
+            apimachinery::pkg::apis::meta::v1::{ObjectMeta, WatchEvent},
+            WatchResponse,
+        };
+
+        let make_pod = |uid: &str| Pod {
+            metadata: ObjectMeta {
+                uid: Some(uid.to_owned()),
+                ..ObjectMeta::default()
+            },
+            ..Pod::default()
+        };
+        let events = [
+            WatchEvent::Added(make_pod("uid0")),
+            WatchEvent::Modified(make_pod("uid0")),
+            WatchEvent::Deleted(make_pod("uid0")),
+        ];
+
+        let mut enc = crate::EncodingState::new(Encoder::new(), Vec::new());
+        enc.encode_all(&events).unwrap();
+
+        let mut dec = crate::State::new(
+            crate::decoder::k8s_openapi::Decoder::<WatchResponse<Pod>>::new(),
+            Vec::new(),
+        );
+        let mut decoded = Vec::new();
+        for chunk in enc.pending().chunks(7) {
+            decoded.extend(
+                dec.process_next_chunk(chunk)
+                    .map(|result| match result.unwrap() {
+                        WatchResponse::Ok(event) => event,
+                        WatchResponse::Other(_) => panic!("expected an event, got something else"),
+                    }),
+            );
+        }
+        assert_eq!(decoded, events);
+        assert!(dec.finish().is_ok());
+    }
+}
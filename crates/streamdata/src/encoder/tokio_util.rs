@@ -0,0 +1,125 @@
+//! [`tokio_util`] integration (i.e. [`tokio_util::codec::Encoder`] support).
+
+use std::marker::PhantomData;
+
+/// The encoder that wraps any [`tokio_util::codec::Encoder`].
+///
+/// The item type has to be specified, since a [`tokio_util::codec::Encoder`]
+/// can encode many.
+pub struct Encoder<T, Item> {
+    /// The inner encoder type.
+    pub inner: T,
+    /// The intermediate buffer for the inner encoder.
+    scratch: bytes::BytesMut,
+    /// The marker for the item type to encode.
+    item: PhantomData<fn(Item)>,
+}
+
+impl<T, Item> Encoder<T, Item> {
+    /// Create a new [`Encoder`] with a given [`tokio_util::codec::Encoder`].
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            scratch: bytes::BytesMut::new(),
+            item: PhantomData,
+        }
+    }
+}
+
+impl<T, Item> std::fmt::Debug for Encoder<T, Item>
+where
+    T: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Encoder")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, Item> Clone for Encoder<T, Item>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone())
+    }
+}
+
+impl<T, Item, Buffer> crate::Encoder<Buffer> for Encoder<T, Item>
+where
+    T: tokio_util::codec::Encoder<Item>,
+    Buffer: crate::Buffer,
+{
+    type Value = Item;
+    type Error = T::Error;
+
+    fn encode(&mut self, value: Self::Value, output: &mut Buffer) -> Result<(), Self::Error> {
+        self.scratch.clear();
+        self.inner.encode(value, &mut self.scratch)?;
+        output.append(&self.scratch);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<Codec, Item>(codec: Codec, items: Vec<Item>)
+    where
+        Codec: tokio_util::codec::Encoder<Item> + tokio_util::codec::Decoder + Clone,
+        <Codec as tokio_util::codec::Encoder<Item>>::Error: std::fmt::Debug,
+        <Codec as tokio_util::codec::Decoder>::Error: std::fmt::Debug,
+        <Codec as tokio_util::codec::Decoder>::Item: PartialEq<Item> + std::fmt::Debug,
+        Item: Clone + std::fmt::Debug,
+    {
+        let mut enc = crate::EncodingState::new(Encoder::new(codec.clone()), Vec::new());
+        enc.encode_all(items.clone()).unwrap();
+
+        let mut dec =
+            crate::State::new(crate::decoder::tokio_util::Decoder::new(codec), Vec::new());
+        let mut decoded = Vec::new();
+        for chunk in enc.pending().chunks(3) {
+            decoded.extend(dec.process_next_chunk(chunk).map(Result::unwrap));
+        }
+        assert_eq!(decoded, items);
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_length_delimited_round_trip() {
+        round_trip(
+            tokio_util::codec::LengthDelimitedCodec::new(),
+            vec![
+                bytes::Bytes::from_static(b"val0"),
+                bytes::Bytes::new(),
+                bytes::Bytes::from_static(b"val2"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_lines_round_trip() {
+        round_trip(
+            tokio_util::codec::LinesCodec::new(),
+            vec!["val0".to_owned(), String::new(), "val2".to_owned()],
+        );
+    }
+
+    #[test]
+    fn test_error() {
+        let mut enc = crate::EncodingState::new(
+            Encoder::new(
+                tokio_util::codec::LengthDelimitedCodec::builder()
+                    .max_frame_length(3)
+                    .new_codec(),
+            ),
+            Vec::new(),
+        );
+
+        enc.encode(bytes::Bytes::from_static(b"val")).unwrap();
+        assert!(enc.encode(bytes::Bytes::from_static(b"val0")).is_err());
+        assert_eq!(enc.pending(), b"\0\0\0\x03val");
+    }
+}
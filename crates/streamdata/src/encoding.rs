@@ -0,0 +1,109 @@
+//! Encoding, i.e. the write side of the stream.
+
+/// [`Encoder`] represents the ability to encode a value into a given buffer
+/// of data.
+///
+/// This mirrors the [`crate::Decoder`]: the encoder is sans-io, and only
+/// appends the encoded data to the buffer, leaving it to the caller to
+/// actually write the data out.
+pub trait Encoder<Output>
+where
+    Output: crate::Buffer,
+{
+    /// The value to encode.
+    type Value;
+
+    /// The error that can occur while encoding the value.
+    type Error;
+
+    /// Encode the value, appending the encoded data to the `output` buffer.
+    ///
+    /// Nothing is appended if the encoding fails.
+    fn encode(&mut self, value: Self::Value, output: &mut Output) -> Result<(), Self::Error>;
+
+    /// Signal that there are no more values to encode, appending any
+    /// trailing data (i.e. the closing delimiters) to the `output` buffer.
+    ///
+    /// The default implementation does nothing.
+    fn finish(&mut self, output: &mut Output) -> Result<(), Self::Error> {
+        let _ = output;
+        Ok(())
+    }
+}
+
+/// The managed encoding state for the stream of data.
+///
+/// The encoded data is accumulated in the buffer until it is written out and
+/// [`EncodingState::consume`]d.
+#[derive(Debug)]
+pub struct EncodingState<Encoder, Buffer> {
+    /// The encoder to use for producing the data.
+    pub encoder: Encoder,
+    /// The buffer containing the encoded data that has not been written out
+    /// yet.
+    pub buffer: Buffer,
+    /// The absolute offset in the stream of the first byte in the buffer,
+    /// i.e. the amount of bytes written out so far.
+    pub offset: usize,
+}
+
+impl<Encoder, Buffer> EncodingState<Encoder, Buffer> {
+    /// Create a new [`EncodingState`] with the given encoder and buffer,
+    /// starting at the beginning of the stream.
+    pub const fn new(encoder: Encoder, buffer: Buffer) -> Self {
+        Self {
+            encoder,
+            buffer,
+            offset: 0,
+        }
+    }
+}
+
+impl<Encoder, Buffer> EncodingState<Encoder, Buffer>
+where
+    Encoder: self::Encoder<Buffer>,
+    Buffer: crate::Buffer,
+{
+    /// Encode the value into the buffer.
+    pub fn encode(&mut self, value: Encoder::Value) -> Result<(), Encoder::Error> {
+        self.encoder.encode(value, &mut self.buffer)
+    }
+
+    /// Encode all the values into the buffer, stopping at the first error.
+    pub fn encode_all<I>(&mut self, values: I) -> Result<(), Encoder::Error>
+    where
+        I: IntoIterator<Item = Encoder::Value>,
+    {
+        values.into_iter().try_for_each(|value| self.encode(value))
+    }
+
+    /// Signal that there are no more values to encode.
+    ///
+    /// See [`self::Encoder::finish`].
+    pub fn finish(&mut self) -> Result<(), Encoder::Error> {
+        self.encoder.finish(&mut self.buffer)
+    }
+}
+
+impl<Encoder, Buffer> EncodingState<Encoder, Buffer>
+where
+    Buffer: crate::Buffer,
+{
+    /// View the encoded data that is pending to be written out.
+    pub fn pending(&self) -> &[u8] {
+        self.buffer.view()
+    }
+
+    /// Drop the given amount of bytes that have been written out from
+    /// the start of the buffer.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn consume(&mut self, bytes: usize) {
+        self.buffer.advance(bytes);
+        self.offset += bytes;
+    }
+
+    /// Returns `true` if there is no encoded data pending to be written out.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
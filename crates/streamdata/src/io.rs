@@ -0,0 +1,363 @@
+//! Blocking [`std::io`] integration.
+//!
+//! Drives the [`State`] from a [`std::io::Read`] or a [`std::io::BufRead`],
+//! for the synchronous code that has no use for the async runtime.
+
+use crate::State;
+
+/// The default size of the chunks to read the [`std::io::Read`] in.
+pub const DEFAULT_CHUNK_SIZE: usize = 8 * 1024;
+
+/// The source of the data chunks for the [`Iter`].
+pub trait Source {
+    /// Read the next chunk of data and pass it to the given fn.
+    ///
+    /// Returns `None` if there is no more data to read.
+    fn next_chunk<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> std::io::Result<Option<R>>;
+}
+
+/// The [`Source`] that reads the [`std::io::Read`] in chunks of a given size.
+#[derive(Debug)]
+pub struct ReadSource<Reader> {
+    /// The reader to read the data from.
+    pub inner: Reader,
+    /// The buffer to read the chunks into.
+    chunk: Vec<u8>,
+}
+
+impl<Reader> ReadSource<Reader> {
+    /// Create a new [`ReadSource`] reading in chunks of
+    /// the [`DEFAULT_CHUNK_SIZE`].
+    pub fn new(inner: Reader) -> Self {
+        Self::with_chunk_size(inner, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Create a new [`ReadSource`] reading in chunks of the given size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `chunk_size` is zero.
+    pub fn with_chunk_size(inner: Reader, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "the chunk size must not be zero");
+        Self {
+            inner,
+            chunk: vec![0; chunk_size],
+        }
+    }
+}
+
+impl<Reader> Source for ReadSource<Reader>
+where
+    Reader: std::io::Read,
+{
+    fn next_chunk<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> std::io::Result<Option<R>> {
+        loop {
+            match self.inner.read(&mut self.chunk) {
+                Ok(0) => return Ok(None),
+                Ok(len) => return Ok(Some(f(&self.chunk[..len]))),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// The [`Source`] that reads the [`std::io::BufRead`] without copying
+/// the data into the intermediate buffer.
+#[derive(Debug)]
+pub struct BufReadSource<Reader> {
+    /// The reader to read the data from.
+    pub inner: Reader,
+}
+
+impl<Reader> BufReadSource<Reader> {
+    /// Create a new [`BufReadSource`].
+    pub const fn new(inner: Reader) -> Self {
+        Self { inner }
+    }
+}
+
+impl<Reader> Source for BufReadSource<Reader>
+where
+    Reader: std::io::BufRead,
+{
+    fn next_chunk<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> std::io::Result<Option<R>> {
+        loop {
+            match self.inner.fill_buf() {
+                Ok([]) => return Ok(None),
+                Ok(chunk) => {
+                    let len = chunk.len();
+                    let result = f(chunk);
+                    self.inner.consume(len);
+                    return Ok(Some(result));
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// The blocking iterator over the values decoded from the data read from
+/// a given [`Source`].
+///
+/// The iteration ends after the first error, unless it is a decoding error
+/// and the state recovers from those (see [`State::recovery`]), in which case
+/// the iteration carries on past the error.
+#[derive(Debug)]
+pub struct Iter<Source, Decoder, Buffer> {
+    /// The source of the data.
+    pub source: Source,
+    /// The decoding state, taken once the iteration is over.
+    state: Option<State<Decoder, Buffer>>,
+    /// Whether the end of input has been reached.
+    eof: bool,
+}
+
+impl<Source, Decoder, Buffer> Iter<Source, Decoder, Buffer> {
+    /// Create a new [`Iter`] reading from the given source into the given
+    /// state.
+    pub const fn new(source: Source, state: State<Decoder, Buffer>) -> Self {
+        Self {
+            source,
+            state: Some(state),
+            eof: false,
+        }
+    }
+}
+
+/// Decode the values from the given [`std::io::Read`], reading it in chunks of
+/// the [`DEFAULT_CHUNK_SIZE`].
+///
+/// Use [`Iter::new`] with [`ReadSource::with_chunk_size`] to read in chunks of
+/// a different size.
+pub fn read<Reader, Decoder, Buffer>(
+    reader: Reader,
+    state: State<Decoder, Buffer>,
+) -> Iter<ReadSource<Reader>, Decoder, Buffer>
+where
+    Reader: std::io::Read,
+{
+    Iter::new(ReadSource::new(reader), state)
+}
+
+/// Decode the values from the given [`std::io::BufRead`].
+pub fn buf_read<Reader, Decoder, Buffer>(
+    reader: Reader,
+    state: State<Decoder, Buffer>,
+) -> Iter<BufReadSource<Reader>, Decoder, Buffer>
+where
+    Reader: std::io::BufRead,
+{
+    Iter::new(BufReadSource::new(reader), state)
+}
+
+impl<Source, Decoder, Buffer> Iterator for Iter<Source, Decoder, Buffer>
+where
+    Source: self::Source,
+    Decoder: crate::Decoder<Buffer>,
+    Buffer: crate::Buffer,
+{
+    type Item = Result<Decoder::Value, Error<Decoder::Error, Buffer>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let state = self.state.as_mut()?;
+            let result = if self.eof {
+                state.process_eof().next()
+            } else {
+                state.available().next()
+            };
+            let error = match result {
+                Some(Ok(value)) => return Some(Ok(value)),
+                // The state resynchronizes upon the next call.
+                Some(Err(err)) if !matches!(state.recovery, crate::Recovery::ShortCircuit) => {
+                    return Some(Err(Error::Decoding(err)))
+                }
+                Some(Err(err)) => Error::Decoding(err),
+                None if self.eof => {
+                    let state = self.state.take()?;
+                    let data = state.finish().err()?;
+                    return Some(Err(Error::UndecodedDataLeftUponCompletion { data }));
+                }
+                None => match self
+                    .source
+                    .next_chunk(|chunk| state.try_process_next_chunk(chunk).map(drop))
+                {
+                    Ok(Some(Ok(()))) => continue,
+                    Ok(Some(Err(err))) => Error::BufferLimitExceeded(err),
+                    Ok(None) => {
+                        self.eof = true;
+                        continue;
+                    }
+                    Err(err) => Error::Reading(err),
+                },
+            };
+            self.state = None;
+            return Some(Err(error));
+        }
+    }
+}
+
+/// Errors that can occur while decoding the data read from the [`Source`].
+#[derive(Debug)]
+pub enum Error<DecoderError, Buffer> {
+    /// An error has occurred while reading the data.
+    Reading(std::io::Error),
+    /// An error has occurred while decoding the values.
+    Decoding(DecoderError),
+    /// The data could not be buffered because of the
+    /// [`State::buffer_limit`].
+    BufferLimitExceeded(crate::BufferLimitExceeded),
+    /// After all the data was read and the decoding was complete there is still
+    /// some data in the state buffer.
+    UndecodedDataLeftUponCompletion {
+        /// The buffer containing the leftover data.
+        data: Buffer,
+    },
+}
+
+impl<DecoderError, Buffer> std::fmt::Display for Error<DecoderError, Buffer>
+where
+    DecoderError: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Reading(err) => write!(f, "reading: {err}"),
+            Self::Decoding(err) => write!(f, "decoding: {err}"),
+            Self::BufferLimitExceeded(err) => write!(f, "buffering: {err}"),
+            Self::UndecodedDataLeftUponCompletion { .. } => write!(
+                f,
+                "some data left in the buffer after the data was read completely"
+            ),
+        }
+    }
+}
+
+impl<DecoderError, Buffer> std::error::Error for Error<DecoderError, Buffer>
+where
+    DecoderError: std::error::Error + 'static,
+    Buffer: std::fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Reading(err) => Some(err),
+            Self::Decoding(err) => Some(err),
+            Self::BufferLimitExceeded(err) => Some(err),
+            Self::UndecodedDataLeftUponCompletion { .. } => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+
+    fn make_state() -> State<crate::decoder::serde_json::Decoder<u32>, Vec<u8>> {
+        State::new(crate::decoder::serde_json::Decoder::new(), Vec::new())
+    }
+
+    /// The reader that fails once after each read.
+    struct Flaky<'a> {
+        /// The data to read.
+        data: &'a [u8],
+        /// The kind of error to fail with.
+        error: std::io::ErrorKind,
+        /// Whether to fail on the next read.
+        fail: bool,
+    }
+
+    impl std::io::Read for Flaky<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.fail = !self.fail;
+            if !self.fail {
+                return Err(self.error.into());
+            }
+            self.data.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        let iter = Iter::new(
+            ReadSource::with_chunk_size(&b"1 22\n333 4444"[..], 3),
+            make_state(),
+        );
+        let values: Result<Vec<_>, _> = iter.collect();
+        assert_eq!(values.unwrap(), [1, 22, 333, 4444]);
+    }
+
+    #[test]
+    fn test_buf_read() {
+        let reader = std::io::BufReader::with_capacity(3, &b"1 22\n333 4444"[..]);
+        let values: Result<Vec<_>, _> = buf_read(reader, make_state()).collect();
+        assert_eq!(values.unwrap(), [1, 22, 333, 4444]);
+    }
+
+    #[test]
+    fn test_leftover_data() {
+        let mut iter = read(&b"1 [2"[..], make_state());
+        assert_eq!(iter.next().unwrap().unwrap(), 1);
+        match iter.next().unwrap().unwrap_err() {
+            Error::UndecodedDataLeftUponCompletion { data } => assert_eq!(data, b" [2"),
+            err => panic!("unexpected error: {err}"),
+        }
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_decoding_error() {
+        let mut iter = read(&b"1 x 2"[..], make_state());
+        assert_eq!(iter.next().unwrap().unwrap(), 1);
+        assert!(matches!(iter.next(), Some(Err(Error::Decoding(_)))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_decoding_error_recovery() {
+        let state = make_state().with_recovery(crate::Recovery::SkipToNewline);
+        let mut iter = Iter::new(ReadSource::with_chunk_size(&b"1\nx 2\n3 4"[..], 2), state);
+        assert_eq!(iter.next().unwrap().unwrap(), 1);
+        assert!(matches!(iter.next(), Some(Err(Error::Decoding(_)))));
+        assert_eq!(iter.next().unwrap().unwrap(), 3);
+        assert_eq!(iter.next().unwrap().unwrap(), 4);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_reading_error() {
+        let reader = Flaky {
+            data: b"1 2 3",
+            error: std::io::ErrorKind::Interrupted,
+            fail: false,
+        };
+        let source = ReadSource::with_chunk_size(reader, 2);
+        let values: Result<Vec<_>, _> = Iter::new(source, make_state()).collect();
+        assert_eq!(values.unwrap(), [1, 2, 3]);
+
+        let reader = Flaky {
+            data: b"1 2 3",
+            error: std::io::ErrorKind::BrokenPipe,
+            fail: false,
+        };
+        let mut iter = Iter::new(ReadSource::with_chunk_size(reader, 2), make_state());
+        assert_eq!(iter.next().unwrap().unwrap(), 1);
+        assert!(matches!(iter.next(), Some(Err(Error::Reading(_)))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_buffer_limit() {
+        let state = make_state().with_buffer_limit(crate::BufferLimit {
+            max_bytes: 4,
+            on_exceeded: crate::OnBufferLimitExceeded::Fail,
+        });
+        let mut iter = Iter::new(ReadSource::with_chunk_size(&b"1 22222"[..], 3), state);
+        assert_eq!(iter.next().unwrap().unwrap(), 1);
+        assert!(matches!(
+            iter.next(),
+            Some(Err(Error::BufferLimitExceeded(_)))
+        ));
+        assert!(iter.next().is_none());
+    }
+}
@@ -0,0 +1,192 @@
+//! Lending decoding, where the values borrow from the buffer.
+
+use crate::{DecodeError, State};
+
+/// [`LendingDecoder`] represents the ability to decode a value that borrows
+/// from the given data.
+///
+/// Unlike the [`crate::Decoder`], the lending decoder does not consume
+/// the data from the buffer itself; instead, it reports the amount of bytes
+/// the decoded value occupies, and these bytes are dropped from the buffer
+/// once the value is no longer borrowed.
+pub trait LendingDecoder {
+    /// The value to decode, borrowing from the data.
+    type Value<'buf>;
+
+    /// The error that can occur while decoding the value.
+    type Error;
+
+    /// Decode (up to one) value from the data, returning the decoded value
+    /// accompanied by the amount of bytes consumed from the `buf` on success,
+    /// or a relevant decoding error.
+    fn decode<'buf>(
+        &mut self,
+        buf: &'buf [u8],
+    ) -> Result<(Self::Value<'buf>, usize), DecodeError<Self::Error>>;
+
+    /// Decode (up to one) value from the data when it is known that no more
+    /// data will arrive.
+    ///
+    /// See [`crate::Decoder::decode_eof`].
+    fn decode_eof<'buf>(
+        &mut self,
+        buf: &'buf [u8],
+    ) -> Result<(Self::Value<'buf>, usize), DecodeError<Self::Error>> {
+        self.decode(buf)
+    }
+}
+
+impl<Decoder, Buffer> State<Decoder, Buffer>
+where
+    Decoder: LendingDecoder,
+    Buffer: crate::Buffer,
+{
+    /// Take the next chunk of data and return the lending iterator over
+    /// the values available with this new data.
+    ///
+    /// The [`State::buffer_limit`] is not enforced here, since there is no
+    /// way to report it being exceeded; use
+    /// [`State::try_process_next_chunk_lending`] for that.
+    pub fn process_next_chunk_lending(&mut self, chunk: &[u8]) -> LendingIter<'_, Decoder, Buffer> {
+        self.buffer.append(chunk);
+        LendingIter::new(self, false)
+    }
+
+    /// Take the next chunk of data and return the lending iterator over
+    /// the values available with this new data, enforcing
+    /// the [`State::buffer_limit`].
+    ///
+    /// See [`State::try_process_next_chunk`].
+    pub fn try_process_next_chunk_lending(
+        &mut self,
+        chunk: &[u8],
+    ) -> Result<LendingIter<'_, Decoder, Buffer>, crate::BufferLimitExceeded> {
+        // The lending decoders do not keep any state over the buffer.
+        self.admit(chunk.len(), |_| ())?;
+        self.buffer.append(chunk);
+        Ok(LendingIter::new(self, false))
+    }
+
+    /// Signal that the end of input has been reached and return the lending
+    /// iterator over the values that can be decoded from the data left in
+    /// the buffer.
+    ///
+    /// See [`State::process_eof`].
+    pub fn process_eof_lending(&mut self) -> LendingIter<'_, Decoder, Buffer> {
+        LendingIter::new(self, true)
+    }
+}
+
+/// Iterate over the data readily available in the state, decoding the values
+/// that borrow from the state buffer on the fly.
+///
+/// Each value borrows the iterator, so it has to be dropped before the next
+/// value can be obtained; the data of the value is dropped from the buffer
+/// upon the next call to [`LendingIter::next`], or when the iterator itself
+/// is dropped.
+///
+/// Follows the same error handling semantics as the [`crate::AvailableIter`].
+pub struct LendingIter<'state, Decoder, Buffer>
+where
+    Decoder: LendingDecoder,
+    Buffer: crate::Buffer,
+{
+    /// A reference to the state.
+    state: &'state mut State<Decoder, Buffer>,
+    /// The amount of bytes to drop from the buffer before decoding the next
+    /// value.
+    pending: usize,
+    /// Short circut on error.
+    short_circut: bool,
+    /// Whether the end of input has been reached.
+    eof: bool,
+}
+
+impl<'state, Decoder, Buffer> LendingIter<'state, Decoder, Buffer>
+where
+    Decoder: LendingDecoder,
+    Buffer: crate::Buffer,
+{
+    /// Create a new [`Self`] for a given state.
+    fn new(state: &'state mut State<Decoder, Buffer>, eof: bool) -> Self {
+        Self {
+            state,
+            pending: 0,
+            short_circut: false,
+            eof,
+        }
+    }
+
+    /// Drop the data of the previously returned value from the buffer.
+    #[allow(clippy::arithmetic_side_effects)]
+    fn flush(&mut self) {
+        if self.pending > 0 {
+            self.state.buffer.advance(self.pending);
+            self.state.offset += self.pending;
+            self.pending = 0;
+        }
+    }
+
+    /// Decode the next value.
+    #[allow(clippy::arithmetic_side_effects, clippy::should_implement_trait)]
+    pub fn next(
+        &mut self,
+    ) -> Option<Result<<Decoder as LendingDecoder>::Value<'_>, <Decoder as LendingDecoder>::Error>>
+    {
+        self.flush();
+        if self.short_circut || !self.state.resync(self.eof) {
+            return None;
+        }
+
+        let state = &mut *self.state;
+        let view = state.buffer.view();
+        // The skipped data is not dropped from the buffer right away, since
+        // the buffer can not be altered while the view is borrowed.
+        let mut skip = 0;
+        loop {
+            let buf = view.get(skip..).unwrap_or_default();
+            let result = if self.eof {
+                state.decoder.decode_eof(buf)
+            } else {
+                state.decoder.decode(buf)
+            };
+            return match result {
+                Ok((value, consumed_bytes)) => {
+                    self.pending = skip + consumed_bytes;
+                    Some(Ok(value))
+                }
+                Err(DecodeError::NeedMoreData) => {
+                    self.pending = skip;
+                    None
+                }
+                Err(DecodeError::SkipData(bytes_to_skip)) => {
+                    skip += bytes_to_skip;
+                    continue; // skip return
+                }
+                Err(DecodeError::Other(error) | DecodeError::Rejected(error)) => {
+                    self.pending = skip;
+                    state.resync = crate::Resync::start(&state.recovery);
+                    self.short_circut = state.resync.is_none();
+                    Some(Err(error))
+                }
+            };
+        }
+    }
+
+    /// The absolute offset in the stream of the first byte in the buffer
+    /// that is not borrowed by the previously returned value.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn offset(&self) -> usize {
+        self.state.offset + self.pending
+    }
+}
+
+impl<'state, Decoder, Buffer> Drop for LendingIter<'state, Decoder, Buffer>
+where
+    Decoder: LendingDecoder,
+    Buffer: crate::Buffer,
+{
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
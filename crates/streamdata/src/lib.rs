@@ -1,19 +1,226 @@
-//! Utilities for decoding stream data.
+//! Utilities for decoding and encoding stream data.
 
-mod buffer;
+pub mod buffer;
 pub mod decoder;
+pub mod encoder;
+mod encoding;
+pub mod io;
+mod lending;
+pub mod transform;
 
-pub use buffer::*;
+pub use buffer::Buffer;
+pub use encoding::*;
+pub use lending::*;
 
 /// The managed decoding state for the stream of data.
+///
+/// The state keeps track of the resynchronization after the decoding errors
+/// in a private field, so it can not be constructed with a struct literal;
+/// use [`State::new`] and the `with_*` builder methods instead.
 #[derive(Debug)]
 pub struct State<Decoder, Buffer> {
     /// The decoder to use for processing the data.
     pub decoder: Decoder,
     /// The buffer containing carried data from the previously decoded chunks.
     pub buffer: Buffer,
+    /// The absolute offset in the stream of the first byte in the buffer,
+    /// i.e. the amount of bytes consumed from the stream so far.
+    pub offset: usize,
+    /// The limit on the amount of bytes the buffer is allowed to hold.
+    ///
+    /// Only enforced by the `try_process_next_*` family of functions, see
+    /// [`Self::try_process_next_chunk`]: the infallible ones have no way to
+    /// report the limit being exceeded, and silently discarding the data
+    /// there would be surprising.
+    pub buffer_limit: Option<BufferLimit>,
+    /// The policy for recovering from the decoding errors.
+    pub recovery: Recovery,
+    /// The resynchronization in progress after a decoding error, if any.
+    resync: Option<Resync>,
+}
+
+impl<Decoder, Buffer> State<Decoder, Buffer> {
+    /// Create a new [`State`] with the given decoder and buffer, starting at
+    /// the beginning of the stream.
+    pub const fn new(decoder: Decoder, buffer: Buffer) -> Self {
+        Self {
+            decoder,
+            buffer,
+            offset: 0,
+            buffer_limit: None,
+            recovery: Recovery::ShortCircuit,
+            resync: None,
+        }
+    }
+
+    /// Set the limit on the amount of bytes the buffer is allowed to hold.
+    pub fn with_buffer_limit(mut self, buffer_limit: BufferLimit) -> Self {
+        self.buffer_limit = Some(buffer_limit);
+        self
+    }
+
+    /// Set the policy for recovering from the decoding errors.
+    pub fn with_recovery(mut self, recovery: Recovery) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// Continue decoding the same stream with another decoder, i.e. after
+    /// a protocol upgrade.
+    ///
+    /// The buffered data that has not been decoded yet, the offset,
+    /// the buffer limit and the recovery policy are carried over to the new
+    /// state, and the previous decoder is returned along with it.
+    /// The resynchronization in progress, if any, is abandoned, since
+    /// the new decoder is not the one that has failed.
+    ///
+    /// The previous decoder is not given a chance to finish: no
+    /// [`Decoder::decode_eof`] is called, as the buffered data belongs to
+    /// the new decoder. Any partial progress the previous decoder has made
+    /// over the buffered data is discarded, so drain the values that are
+    /// already available (see [`State::available`]) before switching, and
+    /// [`Decoder::reset`] the returned decoder before reusing it.
+    ///
+    /// See [`decoder::two_phase`] for switching the decoders from within
+    /// the decoding.
+    pub fn switch_decoder<NewDecoder>(
+        self,
+        decoder: NewDecoder,
+    ) -> (Decoder, State<NewDecoder, Buffer>) {
+        let Self {
+            decoder: previous,
+            buffer,
+            offset,
+            buffer_limit,
+            recovery,
+            resync: _,
+        } = self;
+        let state = State {
+            decoder,
+            buffer,
+            offset,
+            buffer_limit,
+            recovery,
+            resync: None,
+        };
+        (previous, state)
+    }
+}
+
+/// The policy for recovering from the decoding errors (i.e.
+/// [`DecodeError::Other`]) in the [`State`].
+///
+/// With any policy other than [`Recovery::ShortCircuit`], each error is still
+/// reported, but the iterator then drops the data as prescribed by
+/// the policy, and carries on decoding.
+/// If the data to drop spans beyond the data that is currently in the buffer,
+/// the resynchronization continues as more data arrives.
+///
+/// The data is dropped starting from the start of the buffer at the time of
+/// the error, so the policies are meant for the decoders that leave
+/// the offending data in the buffer when failing; no data is dropped after
+/// the [`DecodeError::Rejected`] errors.
+/// At least one byte is always dropped, so that the decoding makes progress
+/// even with the degenerate policies, i.e. [`Recovery::SkipBytes`] of zero
+/// bytes or [`Recovery::SkipToPattern`] with an empty pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Recovery {
+    /// Stop decoding on the first error, leaving the buffer intact.
+    #[default]
+    ShortCircuit,
+    /// Drop the data up to and including the next newline (`\n`), ignoring
+    /// the newline at the very start of the buffer.
+    ///
+    /// Suitable for the line-delimited formats, i.e. NDJSON.
+    SkipToNewline,
+    /// Drop the data up to (but not including) the next occurrence of
+    /// the given sync pattern, ignoring the occurrence at the very start of
+    /// the buffer.
+    SkipToPattern(Vec<u8>),
+    /// Drop the given amount of bytes.
+    SkipBytes(usize),
+}
+
+/// The resynchronization in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resync {
+    /// Looking for the pattern of the [`Recovery`] policy, starting from
+    /// the given position in the buffer.
+    Pattern {
+        /// The position in the buffer to start looking from.
+        from: usize,
+    },
+    /// Dropping the given amount of bytes.
+    Bytes(usize),
+}
+
+impl Resync {
+    /// Start the resynchronization according to the given policy.
+    ///
+    /// Returns `None` if the policy is to not recover.
+    fn start(recovery: &Recovery) -> Option<Self> {
+        match *recovery {
+            Recovery::ShortCircuit => None,
+            Recovery::SkipToPattern(ref pattern) if pattern.is_empty() => Some(Self::Bytes(1)),
+            Recovery::SkipToNewline | Recovery::SkipToPattern(_) => Some(Self::Pattern { from: 1 }),
+            Recovery::SkipBytes(bytes) => Some(Self::Bytes(bytes.max(1))),
+        }
+    }
 }
 
+/// The limit on the amount of bytes buffered in the [`State`].
+///
+/// The limit applies to the data left undecoded after the previous chunks
+/// were decoded, so a chunk is never rejected just for being large, and
+/// the buffer holds at most `max_bytes` plus the size of the last chunk.
+/// The output of a [`transform::Transform`] is, however, limited as it is
+/// produced, since it is not bounded by the size of the chunk (see
+/// [`transform::Transformed`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferLimit {
+    /// The maximum amount of undecoded bytes the buffer is allowed to hold.
+    pub max_bytes: usize,
+    /// What to do when the limit is exceeded.
+    pub on_exceeded: OnBufferLimitExceeded,
+}
+
+/// The action to take when the [`BufferLimit`] is exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnBufferLimitExceeded {
+    /// Reject the next chunk with the [`BufferLimitExceeded`] error, leaving
+    /// the state intact.
+    Fail,
+    /// Drop the pending data and carry on with the next chunk.
+    ///
+    /// The next chunk is likely to start in the middle of the dropped value,
+    /// so this is meant to be used along with a [`Recovery`] policy.
+    Discard,
+}
+
+/// The error indicating that the data left undecoded in the [`State`] buffer
+/// exceeds the [`BufferLimit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferLimitExceeded {
+    /// The amount of bytes pending in the buffer.
+    pub pending: usize,
+    /// The size of the chunk that was rejected.
+    pub chunk: usize,
+    /// The configured limit.
+    pub max_bytes: usize,
+}
+
+impl std::fmt::Display for BufferLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "buffer limit of {} bytes exceeded: {} bytes pending, {} bytes incoming",
+            self.max_bytes, self.pending, self.chunk
+        )
+    }
+}
+
+impl std::error::Error for BufferLimitExceeded {}
+
 /// The decoder error.
 /// This can either be any error that indicates that more data is needed
 /// to decode the value, i.e. the data is empty or is an incomplete part of
@@ -27,14 +234,28 @@ pub enum DecodeError<T> {
     SkipData(usize),
     /// Some other error has occured.
     Other(T),
+    /// The decoder was unable to decode the data, and has consumed it
+    /// already, i.e. along with the frame it came in.
+    ///
+    /// Unlike with [`DecodeError::Other`], the decoding can carry on right
+    /// away without dropping any more data, so the [`Recovery`] policy only
+    /// tells whether to stop on the error.
+    Rejected(T),
 }
 
 /// The results of the successful decoding.
+#[derive(Debug)]
 pub struct Decoded<T> {
     /// The decoded value.
     pub value: T,
     /// The amount of bytes consumed from the buffer.
     pub consumed_bytes: usize,
+    /// The absolute offset in the stream of the first byte consumed while
+    /// decoding the value.
+    pub start: usize,
+    /// The absolute offset in the stream right after the last byte consumed
+    /// while decoding the value.
+    pub end: usize,
 }
 
 /// [`Decoder`] represents the ability to decode a value from a given buffer
@@ -53,6 +274,54 @@ where
     /// accompanied by the amount of bytes consumed from the `buf` on success,
     /// or a relevant decoding error.
     fn decode(&mut self, input: &mut Input) -> Result<Self::Value, DecodeError<Self::Error>>;
+
+    /// Decode (up to one) value from the buffer when it is known that no more
+    /// data will arrive.
+    ///
+    /// This allows decoding the formats where the last value is terminated by
+    /// the end of the input rather than by some delimiter, i.e. a line without
+    /// the trailing newline.
+    ///
+    /// Returning [`DecodeError::NeedMoreData`] here indicates that there are
+    /// no more values to decode; any data left in the buffer at this point
+    /// is considered undecodable.
+    ///
+    /// The default implementation simply calls [`Self::decode`].
+    fn decode_eof(&mut self, input: &mut Input) -> Result<Self::Value, DecodeError<Self::Error>> {
+        self.decode(input)
+    }
+
+    /// Forget any progress made on the data in the buffer so far.
+    ///
+    /// The decoders that keep track of the buffered data across the calls
+    /// (i.e. to avoid rescanning it) must discard that state here, as the
+    /// buffer is about to be changed from outside of the decoder; the
+    /// [`State`] calls this before skipping the data after a decoding error
+    /// and before discarding the data over the [`BufferLimit`].
+    ///
+    /// The default implementation does nothing.
+    fn reset(&mut self) {}
+}
+
+impl<T, Input> Decoder<Input> for Box<T>
+where
+    T: Decoder<Input> + ?Sized,
+    Input: self::Buffer,
+{
+    type Value = T::Value;
+    type Error = T::Error;
+
+    fn decode(&mut self, input: &mut Input) -> Result<Self::Value, DecodeError<Self::Error>> {
+        (**self).decode(input)
+    }
+
+    fn decode_eof(&mut self, input: &mut Input) -> Result<Self::Value, DecodeError<Self::Error>> {
+        (**self).decode_eof(input)
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
 }
 
 impl<Decoder, Buffer> State<Decoder, Buffer>
@@ -62,9 +331,236 @@ where
 {
     /// Take the next chunk of data and return the iterator over the values
     /// available with this new data.
+    ///
+    /// The [`Self::buffer_limit`] is not enforced here, since there is no way
+    /// to report it being exceeded; use [`Self::try_process_next_chunk`] for
+    /// that.
     pub fn process_next_chunk(&mut self, chunk: &[u8]) -> AvailableIter<'_, Decoder, Buffer> {
         self.buffer.append(chunk);
-        AvailableIter::new(self)
+        AvailableIter::new(self, false)
+    }
+
+    /// Take the next chunk of data in the form of a [`bytes::Buf`] and return
+    /// the iterator over the values available with this new data.
+    ///
+    /// Unlike [`Self::process_next_chunk`], this allows the buffer to take
+    /// the data without copying when possible (see [`Buffer::append_buf`]).
+    ///
+    /// The [`Self::buffer_limit`] is not enforced here, since there is no way
+    /// to report it being exceeded; use [`Self::try_process_next_buf`] for
+    /// that.
+    #[cfg(feature = "bytes")]
+    pub fn process_next_buf<B>(&mut self, buf: B) -> AvailableIter<'_, Decoder, Buffer>
+    where
+        B: bytes::Buf,
+    {
+        self.buffer.append_buf(buf);
+        AvailableIter::new(self, false)
+    }
+
+    /// Take the next chunk of data and return the iterator over the values
+    /// available with this new data, enforcing the [`Self::buffer_limit`].
+    ///
+    /// If the data left undecoded after the previous chunks exceeds the limit,
+    /// either returns the [`BufferLimitExceeded`] error without altering
+    /// the state, or discards that data before taking the chunk (see
+    /// [`OnBufferLimitExceeded`]).
+    /// Drain the returned iterator before passing the next chunk, so that
+    /// the decodable data is not counted towards the limit.
+    pub fn try_process_next_chunk(
+        &mut self,
+        chunk: &[u8],
+    ) -> Result<AvailableIter<'_, Decoder, Buffer>, BufferLimitExceeded> {
+        self.admit(chunk.len(), Decoder::reset)?;
+        self.buffer.append(chunk);
+        Ok(AvailableIter::new(self, false))
+    }
+
+    /// Take the next chunk of data in the form of a [`bytes::Buf`] and return
+    /// the iterator over the values available with this new data, enforcing
+    /// the [`Self::buffer_limit`].
+    ///
+    /// See [`Self::try_process_next_chunk`] and [`Self::process_next_buf`].
+    #[cfg(feature = "bytes")]
+    pub fn try_process_next_buf<B>(
+        &mut self,
+        buf: B,
+    ) -> Result<AvailableIter<'_, Decoder, Buffer>, BufferLimitExceeded>
+    where
+        B: bytes::Buf,
+    {
+        self.admit(buf.remaining(), Decoder::reset)?;
+        self.buffer.append_buf(buf);
+        Ok(AvailableIter::new(self, false))
+    }
+
+    /// Append the data to the buffer, as long as it fits within
+    /// the [`Self::buffer_limit`] along with the pending data.
+    ///
+    /// With [`OnBufferLimitExceeded::Discard`], the pending data is dropped
+    /// instead, and so is the data itself if it does not fit on its own.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub(crate) fn append_within_limit(&mut self, data: &[u8]) -> Result<(), BufferLimitExceeded> {
+        let pending = self.buffer.len();
+        let limit = match self.buffer_limit {
+            Some(limit) if pending.saturating_add(data.len()) > limit.max_bytes => limit,
+            _ => {
+                self.buffer.append(data);
+                return Ok(());
+            }
+        };
+        match limit.on_exceeded {
+            OnBufferLimitExceeded::Fail => Err(BufferLimitExceeded {
+                pending,
+                chunk: data.len(),
+                max_bytes: limit.max_bytes,
+            }),
+            OnBufferLimitExceeded::Discard => {
+                tracing::warn!(
+                    pending,
+                    chunk = data.len(),
+                    max_bytes = limit.max_bytes,
+                    "buffer limit exceeded, discarding the data"
+                );
+                self.decoder.reset();
+                self.buffer.advance(pending);
+                self.offset += pending;
+                if data.len() <= limit.max_bytes {
+                    self.buffer.append(data);
+                } else {
+                    self.offset += data.len();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Return the iterator over the values available with the data that is
+    /// already in the buffer.
+    ///
+    /// This is useful when the data is appended to the buffer directly.
+    pub fn available(&mut self) -> AvailableIter<'_, Decoder, Buffer> {
+        AvailableIter::new(self, false)
+    }
+
+    /// Signal that the end of input has been reached and return
+    /// the iterator over the values that can be decoded from the data left in
+    /// the buffer.
+    ///
+    /// Call this after the last chunk has been processed and before
+    /// [`Self::finish`] to let the decoder flush the values that are only
+    /// complete at the end of input (see [`Decoder::decode_eof`]).
+    pub fn process_eof(&mut self) -> AvailableIter<'_, Decoder, Buffer> {
+        AvailableIter::new(self, true)
+    }
+}
+
+impl<Decoder, Buffer> State<Decoder, Buffer>
+where
+    Buffer: self::Buffer,
+{
+    /// Check whether the pending data is within the [`Self::buffer_limit`]
+    /// before taking a chunk of the given size.
+    ///
+    /// The incoming chunk is deliberately not counted: its size is bounded by
+    /// the caller, and it may well consist of complete values only, while
+    /// the data left undecoded is what grows without bound on a malicious
+    /// input (see [`BufferLimit`]).
+    ///
+    /// The decoder is reset with the given function before the data is
+    /// discarded.
+    #[allow(clippy::arithmetic_side_effects)]
+    fn admit(
+        &mut self,
+        chunk: usize,
+        reset: impl FnOnce(&mut Decoder),
+    ) -> Result<(), BufferLimitExceeded> {
+        let Some(limit) = self.buffer_limit else {
+            return Ok(());
+        };
+        let pending = self.buffer.len();
+        if pending <= limit.max_bytes {
+            return Ok(());
+        }
+        match limit.on_exceeded {
+            OnBufferLimitExceeded::Fail => Err(BufferLimitExceeded {
+                pending,
+                chunk,
+                max_bytes: limit.max_bytes,
+            }),
+            OnBufferLimitExceeded::Discard => {
+                tracing::warn!(
+                    pending,
+                    chunk,
+                    max_bytes = limit.max_bytes,
+                    "buffer limit exceeded, discarding the data"
+                );
+                reset(&mut self.decoder);
+                self.buffer.advance(pending);
+                self.offset += pending;
+                Ok(())
+            }
+        }
+    }
+
+    /// Continue the resynchronization after a decoding error, if any.
+    ///
+    /// Returns `true` if the resynchronization is complete, or `false` if it
+    /// needs more data.
+    #[allow(clippy::arithmetic_side_effects)]
+    fn resync(&mut self, eof: bool) -> bool {
+        let Some(resync) = self.resync.take() else {
+            return true;
+        };
+        let view = self.buffer.view();
+        let (skip, left) = match resync {
+            Resync::Bytes(bytes) => {
+                let skip = bytes.min(view.len());
+                (skip, Some(Resync::Bytes(bytes - skip)))
+            }
+            Resync::Pattern { from } => {
+                let (pattern, inclusive) = match self.recovery {
+                    Recovery::SkipToNewline => (&b"\n"[..], true),
+                    Recovery::SkipToPattern(ref pattern) => (pattern.as_slice(), false),
+                    Recovery::ShortCircuit | Recovery::SkipBytes(_) => (&[][..], false),
+                };
+                let found = view
+                    .get(from..)
+                    .filter(|_| !pattern.is_empty())
+                    .and_then(|rest| {
+                        rest.windows(pattern.len())
+                            .position(|window| window == pattern)
+                    });
+                match found {
+                    Some(position) if inclusive => (from + position + pattern.len(), None),
+                    Some(position) => (from + position, None),
+                    None if pattern.is_empty() => (0, None),
+                    None => {
+                        // Keep the tail that might be the start of the pattern.
+                        let skip = view.len().saturating_sub(pattern.len() - 1);
+                        let left = Resync::Pattern {
+                            from: from.saturating_sub(skip),
+                        };
+                        (skip, Some(left))
+                    }
+                }
+            }
+        };
+        let left = match left {
+            Some(Resync::Bytes(0)) | None => None,
+            Some(_) if eof => {
+                // The data being skipped ends with the input.
+                let len = self.buffer.len();
+                self.buffer.advance(len);
+                self.offset += len;
+                return true;
+            }
+            left => left,
+        };
+        self.buffer.advance(skip);
+        self.offset += skip;
+        self.resync = left;
+        self.resync.is_none()
     }
 
     /// Returns `true` if there is no bufferred data.
@@ -75,7 +571,7 @@ where
     /// obvious reason for which is that decoding would require more data
     /// to arrive to succeed).
     pub fn is_empty(&self) -> bool {
-        self.buffer.view().is_empty()
+        self.buffer.is_empty()
     }
 
     /// Finish the processing.
@@ -101,6 +597,8 @@ where
 /// the iterator, and either give up on the whole [`State`]
 /// (by [`State::finish`]-ing it) or try to correct the state by altering
 /// the `buffer` somehow.
+/// Alternatively, set the [`State::recovery`] policy to have the iterator
+/// drop the offending data and carry on after reporting the error.
 ///
 /// This can be ergonomic when used with `.collect::<Result<Vec<_>, _>`.
 pub struct AvailableIter<'state, Decoder, Buffer>
@@ -114,6 +612,8 @@ where
     state: &'state mut State<Decoder, Buffer>,
     /// Short circut on error.
     short_circut: bool,
+    /// Whether the end of input has been reached.
+    eof: bool,
 }
 
 impl<'state, Decoder, Buffer> Iterator for AvailableIter<'state, Decoder, Buffer>
@@ -127,19 +627,55 @@ where
     >;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.short_circut {
+        self.next_decoded()
+            .map(|result| result.map(|decoded| decoded.value))
+    }
+}
+
+impl<'state, Decoder, Buffer> AvailableIter<'state, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// Decode the next value, keeping track of the position in the stream.
+    #[allow(clippy::arithmetic_side_effects)]
+    fn next_decoded(&mut self) -> Option<<WithOffsets<'state, Decoder, Buffer> as Iterator>::Item> {
+        if self.short_circut || !self.state.resync(self.eof) {
             return None;
         }
         loop {
-            return match self.state.decoder.decode(&mut self.state.buffer) {
-                Ok(value) => Some(Ok(value)),
+            let len_before = self.state.buffer.len();
+            let result = if self.eof {
+                self.state.decoder.decode_eof(&mut self.state.buffer)
+            } else {
+                self.state.decoder.decode(&mut self.state.buffer)
+            };
+            let consumed_bytes = len_before - self.state.buffer.len();
+            let start = self.state.offset;
+            self.state.offset += consumed_bytes;
+            return match result {
+                Ok(value) => Some(Ok(Decoded {
+                    value,
+                    consumed_bytes,
+                    start,
+                    end: self.state.offset,
+                })),
                 Err(DecodeError::NeedMoreData) => None,
                 Err(DecodeError::SkipData(bytes_to_skip)) => {
                     self.state.buffer.advance(bytes_to_skip);
+                    self.state.offset += bytes_to_skip;
                     continue; // skip return
                 }
                 Err(DecodeError::Other(error)) => {
-                    self.short_circut = true;
+                    self.state.resync = Resync::start(&self.state.recovery);
+                    if self.state.resync.is_some() {
+                        self.state.decoder.reset();
+                    }
+                    self.short_circut = self.state.resync.is_none();
+                    Some(Err(error))
+                }
+                Err(DecodeError::Rejected(error)) => {
+                    self.short_circut = self.state.recovery == Recovery::ShortCircuit;
                     Some(Err(error))
                 }
             };
@@ -154,13 +690,28 @@ where
 {
     /// Create a new [`Self`] for a given state.
     /// Private fn for internal use only.
-    fn new(state: &'state mut State<Decoder, Buffer>) -> Self {
+    fn new(state: &'state mut State<Decoder, Buffer>, eof: bool) -> Self {
         Self {
             state,
             short_circut: false,
+            eof,
         }
     }
 
+    /// Switch to yielding the values along with their positions in
+    /// the stream.
+    pub fn with_offsets(self) -> WithOffsets<'state, Decoder, Buffer> {
+        WithOffsets { inner: self }
+    }
+
+    /// The absolute offset in the stream of the first byte in the buffer.
+    ///
+    /// After an error is returned this points at the start of the data that
+    /// could not be decoded.
+    pub fn offset(&self) -> usize {
+        self.state.offset
+    }
+
     /// View access to the state buffer.
     ///
     /// This can be useful for inspecting the buffer after the chunks have beed added to it.
@@ -186,3 +737,47 @@ where
         self.collect()
     }
 }
+
+/// Iterate over the data readily available in the state, decoding the values
+/// on the fly and yielding them as [`Decoded`] along with their positions in
+/// the stream.
+///
+/// Created by [`AvailableIter::with_offsets`]; follows the same error handling
+/// semantics as the [`AvailableIter`].
+pub struct WithOffsets<'state, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// The underlying iterator.
+    inner: AvailableIter<'state, Decoder, Buffer>,
+}
+
+impl<'state, Decoder, Buffer> Iterator for WithOffsets<'state, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    type Item = Result<
+        Decoded<<Decoder as self::Decoder<Buffer>>::Value>,
+        <Decoder as self::Decoder<Buffer>>::Error,
+    >;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_decoded()
+    }
+}
+
+impl<'state, Decoder, Buffer> WithOffsets<'state, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// The absolute offset in the stream of the first byte in the buffer.
+    ///
+    /// After an error is returned this points at the start of the data that
+    /// could not be decoded.
+    pub fn offset(&self) -> usize {
+        self.inner.offset()
+    }
+}
@@ -0,0 +1,515 @@
+//! Byte transformations applied to the data before decoding.
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "deflate",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "lz4"
+))]
+pub mod decompress;
+#[cfg(feature = "encoding_rs")]
+pub mod encoding_rs;
+
+use std::collections::VecDeque;
+
+use crate::{AvailableIter, Buffer, BufferLimitExceeded, Decoder, Recovery, State};
+
+/// [`Sink`] represents the destination of the [`Transform`] output.
+///
+/// Unlike the [`Buffer`], the sink can only be appended to, so the transform
+/// can not alter the data it has already produced.
+pub trait Sink {
+    /// Append the data to the sink.
+    fn append(&mut self, data: &[u8]);
+}
+
+impl<T> Sink for T
+where
+    T: Buffer,
+{
+    fn append(&mut self, data: &[u8]) {
+        Buffer::append(self, data);
+    }
+}
+
+/// [`Transform`] represents the ability to transform the stream of bytes,
+/// i.e. to decompress or to decrypt it.
+///
+/// The transform is sans-io just like the [`Decoder`]: it takes the data in
+/// chunks of arbitrary sizes, keeps whatever state it needs between
+/// the chunks, and writes the output to the given [`Sink`].
+pub trait Transform {
+    /// The error that can occur while transforming the data.
+    type Error;
+
+    /// Transform the next chunk of data, appending the output to the `output`
+    /// sink.
+    ///
+    /// The transform is not required to produce any output for every chunk,
+    /// and may keep the input data internally until more data arrives.
+    fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> Result<(), Self::Error>
+    where
+        Output: Sink;
+
+    /// Signal that the end of input has been reached, appending any
+    /// remaining output to the `output` sink.
+    ///
+    /// This is the place to report the truncated input.
+    ///
+    /// The default implementation does nothing.
+    fn finish<Output>(&mut self, output: &mut Output) -> Result<(), Self::Error>
+    where
+        Output: Sink,
+    {
+        let _ = output;
+        Ok(())
+    }
+}
+
+/// The [`State`] with a [`Transform`] applied to the data before it gets to
+/// the buffer.
+///
+/// The transform errors are reported by the `process_*` functions, and are
+/// thus kept apart from the decoding errors, which are reported by
+/// the iterators.
+/// When the transform fails, the output it has produced before the failure
+/// is kept, and can still be decoded via [`Self::available`].
+///
+/// The [`State::buffer_limit`] is enforced on the transform output as it is
+/// produced, since a small chunk can expand into an arbitrary amount of data
+/// (i.e. a decompression bomb).
+/// Before the output would grow the buffer past the limit, the values that
+/// can be decoded from the buffer are decoded, and are held until
+/// the iterator returned by the `process_*` functions yields them, so that
+/// the limit only applies to the data that can not be decoded yet.
+/// With [`crate::OnBufferLimitExceeded::Fail`], the output past the limit is
+/// dropped and [`Error::BufferLimitExceeded`] is reported, after which
+/// the stream can not be continued.
+pub struct Transformed<Transform, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// The transform to apply.
+    pub transform: Transform,
+    /// The decoding state.
+    pub state: State<Decoder, Buffer>,
+    /// The values decoded to make room for the transform output, which have
+    /// not been yielded yet.
+    decoded: VecDeque<Result<Decoder::Value, Decoder::Error>>,
+}
+
+impl<Transform, Decoder, Buffer> std::fmt::Debug for Transformed<Transform, Decoder, Buffer>
+where
+    Transform: std::fmt::Debug,
+    Decoder: self::Decoder<Buffer> + std::fmt::Debug,
+    Buffer: self::Buffer + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transformed")
+            .field("transform", &self.transform)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Transform, Decoder, Buffer> Transformed<Transform, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// Create a new [`Transformed`] state with the given transform.
+    pub const fn new(transform: Transform, state: State<Decoder, Buffer>) -> Self {
+        Self {
+            transform,
+            state,
+            decoded: VecDeque::new(),
+        }
+    }
+
+    /// Return the iterator over the values available with the data that has
+    /// already been transformed.
+    ///
+    /// See [`State::available`].
+    pub fn available(&mut self) -> TransformedIter<'_, Decoder, Buffer> {
+        TransformedIter {
+            decoded: &mut self.decoded,
+            available: self.state.available(),
+        }
+    }
+}
+
+impl<Transform, Decoder, Buffer> Transformed<Transform, Decoder, Buffer>
+where
+    Transform: self::Transform,
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// Transform the next chunk of data and return the iterator over
+    /// the values available with this new data.
+    pub fn process_next_chunk(
+        &mut self,
+        chunk: &[u8],
+    ) -> Result<TransformedIter<'_, Decoder, Buffer>, Error<Transform::Error>> {
+        let mut output = Output::new(&mut self.state, &mut self.decoded);
+        let result = self.transform.transform(chunk, &mut output);
+        output.check(result)?;
+        Ok(self.available())
+    }
+
+    /// Signal that the end of input has been reached and return
+    /// the iterator over the values that can be decoded from the data left in
+    /// the buffer.
+    ///
+    /// See [`State::process_eof`].
+    pub fn process_eof(
+        &mut self,
+    ) -> Result<TransformedIter<'_, Decoder, Buffer>, Error<Transform::Error>> {
+        let mut output = Output::new(&mut self.state, &mut self.decoded);
+        let result = self.transform.finish(&mut output);
+        output.check(result)?;
+        Ok(TransformedIter {
+            decoded: &mut self.decoded,
+            available: self.state.process_eof(),
+        })
+    }
+}
+
+/// Iterate over the values available in the [`Transformed`] state: first
+/// the ones decoded while the transform output was being appended, then
+/// the ones decoded on the fly from the data left in the buffer.
+///
+/// See [`AvailableIter`].
+pub struct TransformedIter<'state, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// The values decoded in advance.
+    decoded: &'state mut VecDeque<Result<Decoder::Value, Decoder::Error>>,
+    /// The iterator over the rest of the values.
+    available: AvailableIter<'state, Decoder, Buffer>,
+}
+
+impl<'state, Decoder, Buffer> Iterator for TransformedIter<'state, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    type Item = Result<Decoder::Value, Decoder::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decoded.pop_front().or_else(|| self.available.next())
+    }
+}
+
+/// The [`Sink`] passing the transform output to the [`State`] buffer within
+/// the [`State::buffer_limit`].
+struct Output<'a, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// The state to append the output to.
+    state: &'a mut State<Decoder, Buffer>,
+    /// The values decoded to make room for the output.
+    decoded: &'a mut VecDeque<Result<Decoder::Value, Decoder::Error>>,
+    /// The error, if the limit has been exceeded.
+    exceeded: Option<BufferLimitExceeded>,
+}
+
+impl<'a, Decoder, Buffer> Output<'a, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// Create a new [`Output`] for the given state.
+    fn new(
+        state: &'a mut State<Decoder, Buffer>,
+        decoded: &'a mut VecDeque<Result<Decoder::Value, Decoder::Error>>,
+    ) -> Self {
+        Self {
+            state,
+            decoded,
+            exceeded: None,
+        }
+    }
+
+    /// Check whether the data would not fit within the limit along with
+    /// the pending data.
+    fn over_limit(&self, data: &[u8]) -> bool {
+        self.state.buffer_limit.is_some_and(|limit| {
+            self.state.buffer.len().saturating_add(data.len()) > limit.max_bytes
+        })
+    }
+
+    /// Decode the values available in the buffer, unless the decoding is
+    /// stuck on an error.
+    fn decode(&mut self) {
+        let stuck = matches!(self.decoded.back(), Some(Err(_)))
+            && self.state.recovery == Recovery::ShortCircuit;
+        if !stuck {
+            self.decoded.extend(self.state.available());
+        }
+    }
+
+    /// Combine the result of the transform with the limit check.
+    fn check<T>(self, result: Result<(), T>) -> Result<(), Error<T>> {
+        result.map_err(Error::Transform)?;
+        match self.exceeded {
+            Some(err) => Err(Error::BufferLimitExceeded(err)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, Decoder, Buffer> Sink for Output<'a, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    fn append(&mut self, data: &[u8]) {
+        if self.exceeded.is_some() {
+            return;
+        }
+        if self.over_limit(data) {
+            self.decode();
+        }
+        if let Err(err) = self.state.append_within_limit(data) {
+            self.exceeded = Some(err);
+        }
+    }
+}
+
+/// Errors that can occur while processing the data with the [`Transformed`]
+/// state.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<T> {
+    /// The transform has failed.
+    Transform(T),
+    /// The transform output does not fit within the [`State::buffer_limit`].
+    BufferLimitExceeded(BufferLimitExceeded),
+}
+
+impl<T> std::fmt::Display for Error<T>
+where
+    T: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transform(err) => write!(f, "transform: {err}"),
+            Self::BufferLimitExceeded(err) => write!(f, "buffering: {err}"),
+        }
+    }
+}
+
+impl<T> std::error::Error for Error<T>
+where
+    T: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transform(err) => Some(err),
+            Self::BufferLimitExceeded(err) => Some(err),
+        }
+    }
+}
+
+impl<Transform, Decoder, Buffer> Transformed<Transform, Decoder, Buffer>
+where
+    Decoder: self::Decoder<Buffer>,
+    Buffer: self::Buffer,
+{
+    /// Finish the processing.
+    ///
+    /// See [`State::finish`].
+    pub fn finish(self) -> Result<(), Buffer> {
+        self.state.finish()
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    /// Decodes the hex-encoded data.
+    #[derive(Debug, Default)]
+    struct Hex {
+        /// The high nibble of the byte that has been split across the chunks.
+        high: Option<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum HexError {
+        InvalidDigit(u8),
+        Truncated,
+    }
+
+    impl Transform for Hex {
+        type Error = HexError;
+
+        fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> Result<(), HexError>
+        where
+            Output: Sink,
+        {
+            for &digit in input {
+                let nibble = (digit as char)
+                    .to_digit(16)
+                    .and_then(|nibble| u8::try_from(nibble).ok())
+                    .ok_or(HexError::InvalidDigit(digit))?;
+                match self.high.take() {
+                    None => self.high = Some(nibble),
+                    Some(high) => output.append(&[high << 4 | nibble]),
+                }
+            }
+            Ok(())
+        }
+
+        fn finish<Output>(&mut self, _output: &mut Output) -> Result<(), HexError>
+        where
+            Output: Sink,
+        {
+            match self.high {
+                None => Ok(()),
+                Some(_) => Err(HexError::Truncated),
+            }
+        }
+    }
+
+    fn hex(data: &[u8]) -> Vec<u8> {
+        data.iter()
+            .flat_map(|byte| format!("{byte:02x}").into_bytes())
+            .collect()
+    }
+
+    fn make_state() -> Transformed<Hex, crate::decoder::serde_json::Decoder<TO>, Vec<u8>> {
+        Transformed::new(
+            Hex::default(),
+            State::new(crate::decoder::serde_json::Decoder::new(), Vec::new()),
+        )
+    }
+
+    #[test]
+    fn test_chunked() {
+        let mut dec = make_state();
+
+        let data = hex(br#"{"field": "val0"}{"field": "val1"}"#);
+        let (first, second) = data.split_at(35);
+
+        {
+            let mut stream = dec.process_next_chunk(first).unwrap();
+            assert_eq!(stream.next().unwrap().unwrap().field, "val0");
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(dec.transform.high, Some(7));
+
+        {
+            let mut stream = dec.process_next_chunk(second).unwrap();
+            assert_eq!(stream.next().unwrap().unwrap().field, "val1");
+            assert!(stream.next().is_none());
+        }
+
+        {
+            let mut stream = dec.process_eof().unwrap();
+            assert!(stream.next().is_none());
+        }
+
+        assert!(dec.finish().is_ok());
+    }
+
+    #[test]
+    fn test_errors_are_distinguishable() {
+        let mut dec = make_state();
+
+        let mut data = hex(br#"{"field": "val0"}qwe"#);
+        data.push(b'z');
+
+        // The transform error.
+        assert_eq!(
+            dec.process_next_chunk(&data).err(),
+            Some(Error::Transform(HexError::InvalidDigit(b'z')))
+        );
+
+        // The decoder errors.
+        {
+            let mut stream = dec.available();
+            assert_eq!(stream.next().unwrap().unwrap().field, "val0");
+            assert!(stream.next().unwrap().is_err());
+            assert!(stream.next().is_none());
+        }
+
+        assert_eq!(dec.finish().unwrap_err(), b"qwe");
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut dec = make_state();
+
+        assert!(dec.process_next_chunk(b"7").unwrap().next().is_none());
+        assert_eq!(
+            dec.process_eof().err(),
+            Some(Error::Transform(HexError::Truncated))
+        );
+    }
+
+    #[test]
+    fn test_buffer_limit() {
+        let mut dec = make_state();
+        dec.state.buffer_limit = Some(crate::BufferLimit {
+            max_bytes: 24,
+            on_exceeded: crate::OnBufferLimitExceeded::Fail,
+        });
+
+        {
+            let mut stream = dec
+                .process_next_chunk(&hex(br#"{"field": "val0"}{"fi"#))
+                .unwrap();
+            assert_eq!(stream.next().unwrap().unwrap().field, "val0");
+            assert!(stream.next().is_none());
+        }
+
+        // The output is limited as it is produced.
+        assert_eq!(
+            dec.process_next_chunk(&hex(br#"eld": "val1234567890123"}"#))
+                .err(),
+            Some(Error::BufferLimitExceeded(crate::BufferLimitExceeded {
+                pending: 24,
+                chunk: 1,
+                max_bytes: 24,
+            }))
+        );
+        assert_eq!(dec.state.buffer, br#"{"field": "val1234567890"#);
+    }
+    #[test]
+    fn test_buffer_limit_decodes_output() {
+        let mut dec = make_state();
+        dec.state.buffer_limit = Some(crate::BufferLimit {
+            max_bytes: 24,
+            on_exceeded: crate::OnBufferLimitExceeded::Fail,
+        });
+
+        // The chunk expands to way more than the limit, but the values are
+        // decoded as the output is produced.
+        let data = (0..10)
+            .map(|i| format!(r#"{{"field": "val{i}"}}"#))
+            .collect::<String>();
+        let values = dec
+            .process_next_chunk(&hex(data.as_bytes()))
+            .unwrap()
+            .map(|value| value.unwrap().field)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            (0..10).map(|i| format!("val{i}")).collect::<Vec<_>>()
+        );
+        assert!(dec.finish().is_ok());
+    }
+}
@@ -0,0 +1,1519 @@
+//! Streaming decompression.
+//!
+//! Each codec is available behind the cargo feature of the same name:
+//! `gzip`, `deflate` (both raw deflate and zlib), `zstd`, `brotli` and `lz4`
+//! (the frame format).
+//! The [`Auto`] transform detects the codec from the magic bytes.
+//! zlib is only detected on request, since its two byte header is common in
+//! the plain text.
+//!
+//! All the transforms report the [`io::Error`]s, and fail with
+//! the [`io::ErrorKind::UnexpectedEof`] upon [`Transform::finish`] if
+//! the compressed stream is truncated.
+//!
+//! The output is passed to the sink piece by piece as it is produced, with
+//! each piece bounded regardless of the compression ratio, so that
+//! the [`super::Transformed`] buffer limit holds against the decompression
+//! bombs.
+
+use std::io;
+
+use super::{Sink, Transform};
+
+/// The size of the intermediate buffer used for the output, which is also
+/// the most output produced by a single step of the decompression.
+#[cfg(any(feature = "gzip", feature = "deflate", feature = "brotli"))]
+const SCRATCH_SIZE: usize = 32 * 1024;
+
+/// The error for the truncated compressed stream.
+fn truncated(codec: Codec) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("truncated {codec} stream"),
+    )
+}
+
+/// The error for the data following the end of the compressed stream.
+#[cfg(any(feature = "deflate", feature = "brotli", feature = "lz4"))]
+fn trailing_data(codec: Codec) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("trailing data after the end of the {codec} stream"),
+    )
+}
+
+/// The gzip header flag indicating the extra field.
+#[cfg(feature = "gzip")]
+const FEXTRA: u8 = 1 << 2;
+/// The gzip header flag indicating the file name.
+#[cfg(feature = "gzip")]
+const FNAME: u8 = 1 << 3;
+/// The gzip header flag indicating the comment.
+#[cfg(feature = "gzip")]
+const FCOMMENT: u8 = 1 << 4;
+/// The gzip header flag indicating the header CRC.
+#[cfg(feature = "gzip")]
+const FHCRC: u8 = 1 << 1;
+/// The gzip header flags reserved for the future use.
+#[cfg(feature = "gzip")]
+const FRESERVED: u8 = 0b1110_0000;
+
+/// The part of the gzip member (RFC 1952) being read.
+#[cfg(feature = "gzip")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Member {
+    /// The fixed part of the header.
+    Header,
+    /// The length of the extra field.
+    ExtraLength,
+    /// The extra field, with the given amount of bytes left.
+    Extra(usize),
+    /// The zero-terminated file name.
+    Name,
+    /// The zero-terminated comment.
+    Comment,
+    /// The CRC of the header.
+    HeaderCrc,
+    /// The compressed data.
+    Data,
+    /// The trailer with the CRC and the size of the data.
+    Trailer,
+}
+
+/// The gzip decompressor.
+///
+/// Supports multiple concatenated gzip members, as produced by i.e.
+/// the log rotation tools.
+/// The CRC and the size of the data are verified for every member, while
+/// the CRC of the header, if any, is skipped.
+#[cfg(feature = "gzip")]
+#[derive(Debug)]
+pub struct Gzip {
+    /// The part of the member being read, or `None` between the members.
+    member: Option<Member>,
+    /// The header flags of the member that are yet to be handled.
+    flags: u8,
+    /// The fixed-size field read so far.
+    field: Vec<u8>,
+    /// The decompressor of the member data.
+    inflate: Inflate,
+    /// The CRC of the member data.
+    crc: flate2::Crc,
+}
+
+#[cfg(feature = "gzip")]
+impl Gzip {
+    /// Create a new [`Gzip`] decompressor.
+    pub fn new() -> Self {
+        Self {
+            member: None,
+            flags: 0,
+            field: Vec::new(),
+            inflate: Inflate::new(Codec::Deflate),
+            crc: flate2::Crc::new(),
+        }
+    }
+
+    /// Read the fixed-size field from the input, returning whether the field
+    /// is complete.
+    fn fill(&mut self, size: usize, input: &mut &[u8]) -> bool {
+        let take = size.saturating_sub(self.field.len()).min(input.len());
+        let (head, rest) = input.split_at(take);
+        self.field.extend_from_slice(head);
+        *input = rest;
+        self.field.len() == size
+    }
+
+    /// Pick the next part of the header according to the flags that are yet
+    /// to be handled.
+    fn next_field(&mut self) -> Member {
+        const FIELDS: [(u8, Member); 4] = [
+            (FEXTRA, Member::ExtraLength),
+            (FNAME, Member::Name),
+            (FCOMMENT, Member::Comment),
+            (FHCRC, Member::HeaderCrc),
+        ];
+        for (flag, member) in FIELDS {
+            if self.flags & flag != 0 {
+                self.flags &= !flag;
+                return member;
+            }
+        }
+        Member::Data
+    }
+
+    /// Read the part of the member from the input, returning the next part.
+    fn step<Output>(
+        &mut self,
+        member: Member,
+        input: &mut &[u8],
+        output: &mut Output,
+    ) -> io::Result<Member>
+    where
+        Output: Sink,
+    {
+        let next = match member {
+            Member::Header => {
+                let complete = self.fill(10, input);
+                // Reject the garbage early, rather than as a truncated header.
+                let magic = [0x1f, 0x8b, 8];
+                if self
+                    .field
+                    .iter()
+                    .zip(magic)
+                    .any(|(&byte, expected)| byte != expected)
+                {
+                    return Err(invalid_data("invalid gzip header"));
+                }
+                if !complete {
+                    return Ok(member);
+                }
+                match *self.field.as_slice() {
+                    [_, _, _, flags, ..] if flags & FRESERVED == 0 => self.flags = flags,
+                    _ => return Err(invalid_data("invalid gzip header")),
+                }
+                self.next_field()
+            }
+            Member::ExtraLength => {
+                if !self.fill(2, input) {
+                    return Ok(member);
+                }
+                let length = match *self.field.as_slice() {
+                    [low, high] => usize::from(u16::from_le_bytes([low, high])),
+                    _ => 0,
+                };
+                Member::Extra(length)
+            }
+            Member::Extra(left) => {
+                let skip = left.min(input.len());
+                *input = input.get(skip..).unwrap_or_default();
+                match left.saturating_sub(skip) {
+                    0 => self.next_field(),
+                    left => return Ok(Member::Extra(left)),
+                }
+            }
+            Member::Name | Member::Comment => match input.iter().position(|&byte| byte == 0) {
+                Some(position) => {
+                    *input = input.get(position.saturating_add(1)..).unwrap_or_default();
+                    self.next_field()
+                }
+                None => {
+                    *input = &[];
+                    return Ok(member);
+                }
+            },
+            Member::HeaderCrc => {
+                if !self.fill(2, input) {
+                    return Ok(member);
+                }
+                self.next_field()
+            }
+            Member::Data => {
+                let mut output = Checksummed {
+                    crc: &mut self.crc,
+                    output,
+                };
+                let read = self.inflate.inflate(input, &mut output)?;
+                *input = input.get(read..).unwrap_or_default();
+                if !self.inflate.done {
+                    return Ok(member);
+                }
+                Member::Trailer
+            }
+            Member::Trailer => {
+                if !self.fill(8, input) {
+                    return Ok(member);
+                }
+                let (crc, size) = match *self.field.as_slice() {
+                    [a, b, c, d, e, f, g, h] => (
+                        u32::from_le_bytes([a, b, c, d]),
+                        u32::from_le_bytes([e, f, g, h]),
+                    ),
+                    _ => (0, 0),
+                };
+                if crc != self.crc.sum() || size != self.crc.amount() {
+                    return Err(invalid_data("corrupt gzip stream"));
+                }
+                self.inflate.reset();
+                self.crc.reset();
+                Member::Header
+            }
+        };
+        self.field.clear();
+        Ok(next)
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl Default for Gzip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl Transform for Gzip {
+    type Error = io::Error;
+
+    fn transform<Output>(&mut self, mut input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        while !input.is_empty() {
+            let member = self.member.unwrap_or(Member::Header);
+            let next = self.step(member, &mut input, output)?;
+            // The member is complete once the header of the next one is due.
+            self.member = (next != Member::Header || !self.field.is_empty()).then_some(next);
+        }
+        Ok(())
+    }
+
+    fn finish<Output>(&mut self, _output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        match self.member {
+            Some(_) => Err(truncated(Codec::Gzip)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The [`Sink`] keeping track of the CRC of the data passing through.
+#[cfg(feature = "gzip")]
+struct Checksummed<'a, Output> {
+    /// The CRC of the data.
+    crc: &'a mut flate2::Crc,
+    /// The sink to pass the data to.
+    output: &'a mut Output,
+}
+
+#[cfg(feature = "gzip")]
+impl<'a, Output> Sink for Checksummed<'a, Output>
+where
+    Output: Sink,
+{
+    fn append(&mut self, data: &[u8]) {
+        self.crc.update(data);
+        self.output.append(data);
+    }
+}
+
+/// The error for the malformed compressed stream.
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The deflate decompressor, for both the raw deflate and zlib formats.
+#[cfg(any(feature = "gzip", feature = "deflate"))]
+#[derive(Debug)]
+struct Inflate {
+    /// The decompressor.
+    inner: flate2::Decompress,
+    /// The codec, for error reporting.
+    codec: Codec,
+    /// Whether any data has been fed to the decompressor.
+    started: bool,
+    /// Whether the end of the stream has been reached.
+    done: bool,
+    /// The intermediate buffer for the output.
+    scratch: Vec<u8>,
+}
+
+#[cfg(any(feature = "gzip", feature = "deflate"))]
+impl Inflate {
+    /// Create a new [`Inflate`].
+    fn new(codec: Codec) -> Self {
+        Self {
+            inner: flate2::Decompress::new(codec == Codec::Zlib),
+            codec,
+            started: false,
+            done: false,
+            scratch: vec![0; SCRATCH_SIZE],
+        }
+    }
+
+    /// Decompress the data up to the end of the stream, returning the amount
+    /// of input consumed.
+    #[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+    fn inflate<Output>(&mut self, data: &[u8], output: &mut Output) -> io::Result<usize>
+    where
+        Output: Sink,
+    {
+        let mut input = data;
+        // The output may not fit in the scratch buffer, in which case the rest
+        // is kept internally even after all the input has been consumed.
+        let mut full = false;
+        while !self.done && (!input.is_empty() || full) {
+            self.started = true;
+
+            let total_in = self.inner.total_in();
+            let total_out = self.inner.total_out();
+            let status = self
+                .inner
+                .decompress(input, &mut self.scratch, flate2::FlushDecompress::None)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            // The amounts are bounded by the sizes of the slices.
+            let read = (self.inner.total_in() - total_in) as usize;
+            let written = (self.inner.total_out() - total_out) as usize;
+
+            input = input.get(read..).unwrap_or_default();
+            output.append(self.scratch.get(..written).unwrap_or_default());
+            full = written == self.scratch.len();
+
+            match status {
+                flate2::Status::StreamEnd => self.done = true,
+                flate2::Status::Ok | flate2::Status::BufError if read == 0 && written == 0 => {
+                    // No progress is possible, the input is kept internally.
+                    break;
+                }
+                flate2::Status::Ok | flate2::Status::BufError => {}
+            }
+        }
+        Ok(data.len() - input.len())
+    }
+
+    /// Decompress the data, which must not extend past the end of the stream.
+    #[cfg(feature = "deflate")]
+    fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        if self.inflate(input, output)? < input.len() {
+            return Err(trailing_data(self.codec));
+        }
+        Ok(())
+    }
+
+    /// Start over with the next stream.
+    #[cfg(feature = "gzip")]
+    fn reset(&mut self) {
+        self.inner.reset(self.codec == Codec::Zlib);
+        self.started = false;
+        self.done = false;
+    }
+
+    /// Check that the stream is complete.
+    #[cfg(feature = "deflate")]
+    fn finish(&mut self) -> io::Result<()> {
+        if self.started && !self.done {
+            return Err(truncated(self.codec));
+        }
+        Ok(())
+    }
+}
+
+/// The raw deflate (RFC 1951) decompressor.
+///
+/// Note that the `deflate` HTTP content encoding is actually [`Zlib`].
+#[cfg(feature = "deflate")]
+#[derive(Debug)]
+pub struct Deflate {
+    /// The decompressor.
+    inner: Inflate,
+}
+
+#[cfg(feature = "deflate")]
+impl Deflate {
+    /// Create a new [`Deflate`] decompressor.
+    pub fn new() -> Self {
+        Self {
+            inner: Inflate::new(Codec::Deflate),
+        }
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl Default for Deflate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl Transform for Deflate {
+    type Error = io::Error;
+
+    fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        self.inner.transform(input, output)
+    }
+
+    fn finish<Output>(&mut self, _output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        self.inner.finish()
+    }
+}
+
+/// The zlib (RFC 1950) decompressor.
+#[cfg(feature = "deflate")]
+#[derive(Debug)]
+pub struct Zlib {
+    /// The decompressor.
+    inner: Inflate,
+}
+
+#[cfg(feature = "deflate")]
+impl Zlib {
+    /// Create a new [`Zlib`] decompressor.
+    pub fn new() -> Self {
+        Self {
+            inner: Inflate::new(Codec::Zlib),
+        }
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl Default for Zlib {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl Transform for Zlib {
+    type Error = io::Error;
+
+    fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        self.inner.transform(input, output)
+    }
+
+    fn finish<Output>(&mut self, _output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        self.inner.finish()
+    }
+}
+
+/// The zstd decompressor.
+///
+/// Supports multiple concatenated zstd frames.
+#[cfg(feature = "zstd")]
+pub struct Zstd {
+    /// The decompressor.
+    inner: zstd::stream::raw::Decoder<'static>,
+    /// Whether the last frame has been completed.
+    frame_complete: bool,
+    /// The intermediate buffer for the output.
+    scratch: Vec<u8>,
+}
+
+#[cfg(feature = "zstd")]
+impl std::fmt::Debug for Zstd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Zstd")
+            .field("frame_complete", &self.frame_complete)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Zstd {
+    /// Create a new [`Zstd`] decompressor.
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            inner: zstd::stream::raw::Decoder::new()?,
+            frame_complete: true,
+            scratch: vec![0; zstd::zstd_safe::DCtx::out_size()],
+        })
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Transform for Zstd {
+    type Error = io::Error;
+
+    fn transform<Output>(&mut self, mut input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        use zstd::stream::raw::Operation as _;
+
+        loop {
+            let status = self.inner.run_on_buffers(input, &mut self.scratch)?;
+            input = input.get(status.bytes_read..).unwrap_or_default();
+            output.append(self.scratch.get(..status.bytes_written).unwrap_or_default());
+            self.frame_complete = status.remaining == 0;
+
+            // Keep going while there is input, or while the output might
+            // have been limited by the scratch size.
+            if input.is_empty() && status.bytes_written < self.scratch.len() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn finish<Output>(&mut self, _output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        if !self.frame_complete {
+            return Err(truncated(Codec::Zstd));
+        }
+        Ok(())
+    }
+}
+
+/// The brotli decompressor.
+#[cfg(feature = "brotli")]
+pub struct Brotli {
+    /// The decoder state.
+    inner: brotli::BrotliState<
+        brotli::HeapAlloc<u8>,
+        brotli::HeapAlloc<u32>,
+        brotli::HeapAlloc<brotli::HuffmanCode>,
+    >,
+    /// Whether any data has been fed to the decoder.
+    started: bool,
+    /// Whether the end of the stream has been reached.
+    done: bool,
+    /// The total amount of the output, maintained by the decoder.
+    total_out: usize,
+    /// The intermediate buffer for the output.
+    scratch: Vec<u8>,
+}
+
+#[cfg(feature = "brotli")]
+impl std::fmt::Debug for Brotli {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Brotli")
+            .field("started", &self.started)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "brotli")]
+impl Brotli {
+    /// Create a new [`Brotli`] decompressor.
+    pub fn new() -> Self {
+        Self {
+            inner: brotli::BrotliState::new(
+                brotli::HeapAlloc::new(0),
+                brotli::HeapAlloc::new(0),
+                brotli::HeapAlloc::new(brotli::HuffmanCode::default()),
+            ),
+            started: false,
+            done: false,
+            total_out: 0,
+            scratch: vec![0; SCRATCH_SIZE],
+        }
+    }
+}
+
+#[cfg(feature = "brotli")]
+impl Default for Brotli {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "brotli")]
+impl Transform for Brotli {
+    type Error = io::Error;
+
+    fn transform<Output>(&mut self, mut input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        // The output may not fit in the scratch buffer, in which case the rest
+        // is kept internally even after all the input has been consumed.
+        let mut full = false;
+        while !input.is_empty() || full {
+            if self.done {
+                if input.is_empty() {
+                    break;
+                }
+                return Err(trailing_data(Codec::Brotli));
+            }
+            self.started = true;
+
+            let mut available_in = input.len();
+            let mut read = 0;
+            let mut available_out = self.scratch.len();
+            let mut written = 0;
+            let result = brotli::BrotliDecompressStream(
+                &mut available_in,
+                &mut read,
+                input,
+                &mut available_out,
+                &mut written,
+                &mut self.scratch,
+                &mut self.total_out,
+                &mut self.inner,
+            );
+
+            input = input.get(read..).unwrap_or_default();
+            output.append(self.scratch.get(..written).unwrap_or_default());
+            full = false;
+
+            match result {
+                brotli::BrotliResult::ResultSuccess => self.done = true,
+                brotli::BrotliResult::NeedsMoreOutput => full = true,
+                // All the input is kept internally.
+                brotli::BrotliResult::NeedsMoreInput => break,
+                brotli::BrotliResult::ResultFailure => {
+                    return Err(invalid_data("invalid brotli stream"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish<Output>(&mut self, _output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        if self.started && !self.done {
+            return Err(truncated(Codec::Brotli));
+        }
+        Ok(())
+    }
+}
+
+/// The magic number of the LZ4 frame.
+#[cfg(feature = "lz4")]
+const LZ4_MAGIC: u32 = 0x184D_2204;
+
+/// The mask for the magic numbers of the skippable frames.
+#[cfg(feature = "lz4")]
+const LZ4_SKIPPABLE_MAGIC_MASK: u32 = 0xFFFF_FFF0;
+
+/// The magic number of the skippable frames, with the mask applied.
+#[cfg(feature = "lz4")]
+const LZ4_SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+
+/// The max size of the window the linked blocks refer to.
+#[cfg(feature = "lz4")]
+const LZ4_WINDOW_SIZE: usize = 64 * 1024;
+
+/// The LZ4 frame format decompressor.
+///
+/// Supports multiple concatenated frames and the skippable frames.
+/// The checksums are not verified, and the frames that require
+/// a dictionary are not supported.
+#[cfg(feature = "lz4")]
+#[derive(Debug, Default)]
+pub struct Lz4 {
+    /// The input that has not been processed yet.
+    input: Vec<u8>,
+    /// The position in the format.
+    state: Lz4State,
+    /// The recent output the linked blocks can refer to.
+    window: Vec<u8>,
+    /// The intermediate buffer for the output.
+    scratch: Vec<u8>,
+}
+
+/// The position in the LZ4 frame format.
+#[cfg(feature = "lz4")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Lz4State {
+    /// Expecting the magic number of the next frame.
+    #[default]
+    Magic,
+    /// Expecting the frame descriptor.
+    Descriptor,
+    /// Expecting the next block of the frame.
+    Block(Lz4Frame),
+    /// Expecting the content checksum.
+    ContentChecksum,
+    /// Skipping the given amount of bytes of a skippable frame.
+    Skip(usize),
+}
+
+/// The parameters of the LZ4 frame.
+#[cfg(feature = "lz4")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Lz4Frame {
+    /// Whether the blocks are independent.
+    independent: bool,
+    /// Whether the blocks are followed by the checksum.
+    block_checksum: bool,
+    /// Whether the frame ends with the content checksum.
+    content_checksum: bool,
+    /// The max size of the decompressed block.
+    max_block_size: usize,
+}
+
+/// Read the little-endian [`u32`] at the start of the data, if there is
+/// enough data.
+#[cfg(feature = "lz4")]
+fn read_u32(data: &[u8]) -> Option<u32> {
+    let bytes = data.get(..4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// The error for the malformed LZ4 data.
+#[cfg(feature = "lz4")]
+fn invalid_lz4(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid lz4 frame: {message}"),
+    )
+}
+
+#[cfg(feature = "lz4")]
+impl Lz4 {
+    /// Create a new [`Lz4`] decompressor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process (up to) one element of the format from the start of
+    /// the data.
+    ///
+    /// Returns the amount of bytes consumed, or `None` if more data is
+    /// needed.
+    #[allow(clippy::arithmetic_side_effects)]
+    fn step<Output>(&mut self, data: &[u8], output: &mut Output) -> io::Result<Option<usize>>
+    where
+        Output: Sink,
+    {
+        match self.state {
+            Lz4State::Magic => {
+                let Some(magic) = read_u32(data) else {
+                    return Ok(None);
+                };
+                if magic == LZ4_MAGIC {
+                    self.state = Lz4State::Descriptor;
+                    return Ok(Some(4));
+                }
+                if magic & LZ4_SKIPPABLE_MAGIC_MASK != LZ4_SKIPPABLE_MAGIC {
+                    return Err(trailing_data(Codec::Lz4));
+                }
+                let Some(size) = data.get(4..).and_then(read_u32) else {
+                    return Ok(None);
+                };
+                self.state = Lz4State::Skip(size as usize);
+                Ok(Some(8))
+            }
+            Lz4State::Descriptor => {
+                let &[flags, block_descriptor, ..] = data else {
+                    return Ok(None);
+                };
+                if flags >> 6 != 0b01 {
+                    return Err(invalid_lz4("unsupported version"));
+                }
+                if flags & 0b1 != 0 {
+                    return Err(invalid_lz4("dictionaries are not supported"));
+                }
+                let max_block_size = match (block_descriptor >> 4) & 0b111 {
+                    4 => 64 * 1024,
+                    5 => 256 * 1024,
+                    6 => 1024 * 1024,
+                    7 => 4 * 1024 * 1024,
+                    _ => return Err(invalid_lz4("invalid block max size")),
+                };
+                let content_size = flags & 0b1000 != 0;
+                // Flags, block descriptor, optional content size and
+                // the header checksum.
+                let len = 2 + if content_size { 8 } else { 0 } + 1;
+                if data.len() < len {
+                    return Ok(None);
+                }
+                self.window.clear();
+                self.state = Lz4State::Block(Lz4Frame {
+                    independent: flags & 0b10_0000 != 0,
+                    block_checksum: flags & 0b1_0000 != 0,
+                    content_checksum: flags & 0b100 != 0,
+                    max_block_size,
+                });
+                Ok(Some(len))
+            }
+            Lz4State::Block(frame) => {
+                let Some(header) = read_u32(data) else {
+                    return Ok(None);
+                };
+                if header == 0 {
+                    self.state = if frame.content_checksum {
+                        Lz4State::ContentChecksum
+                    } else {
+                        Lz4State::Magic
+                    };
+                    return Ok(Some(4));
+                }
+                let uncompressed = header & 0x8000_0000 != 0;
+                let size = (header & 0x7FFF_FFFF) as usize;
+                if size > frame.max_block_size {
+                    return Err(invalid_lz4("block is too large"));
+                }
+                let len = 4 + size + if frame.block_checksum { 4 } else { 0 };
+                let Some(block) = data.get(4..4 + size).filter(|_| data.len() >= len) else {
+                    return Ok(None);
+                };
+                let block = if uncompressed {
+                    block
+                } else {
+                    self.scratch.resize(frame.max_block_size, 0);
+                    let dict: &[u8] = if frame.independent { &[] } else { &self.window };
+                    let written =
+                        lz4_flex::block::decompress_into_with_dict(block, &mut self.scratch, dict)
+                            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                    self.scratch.get(..written).unwrap_or_default()
+                };
+                output.append(block);
+                if !frame.independent {
+                    self.window.extend_from_slice(block);
+                    let excess = self.window.len().saturating_sub(LZ4_WINDOW_SIZE);
+                    self.window.drain(..excess);
+                }
+                Ok(Some(len))
+            }
+            Lz4State::ContentChecksum => {
+                if data.len() < 4 {
+                    return Ok(None);
+                }
+                self.state = Lz4State::Magic;
+                Ok(Some(4))
+            }
+            Lz4State::Skip(size) => {
+                if data.is_empty() && size > 0 {
+                    return Ok(None);
+                }
+                let skip = size.min(data.len());
+                self.state = match size - skip {
+                    0 => Lz4State::Magic,
+                    left => Lz4State::Skip(left),
+                };
+                Ok(Some(skip))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl Transform for Lz4 {
+    type Error = io::Error;
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        self.input.extend_from_slice(input);
+        let mut data = std::mem::take(&mut self.input);
+        let mut consumed = 0;
+        let result = loop {
+            match self.step(data.get(consumed..).unwrap_or_default(), output) {
+                Ok(Some(len)) => consumed += len,
+                Ok(None) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        data.drain(..consumed);
+        self.input = data;
+        result
+    }
+
+    fn finish<Output>(&mut self, _output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        if self.state != Lz4State::Magic || !self.input.is_empty() {
+            return Err(truncated(Codec::Lz4));
+        }
+        Ok(())
+    }
+}
+
+/// The compression codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Not compressed.
+    None,
+    /// gzip.
+    Gzip,
+    /// Raw deflate.
+    Deflate,
+    /// zlib.
+    Zlib,
+    /// zstd.
+    Zstd,
+    /// brotli.
+    Brotli,
+    /// LZ4 frame format.
+    Lz4,
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::None => "uncompressed",
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Zlib => "zlib",
+            Self::Zstd => "zstd",
+            Self::Brotli => "brotli",
+            Self::Lz4 => "lz4",
+        })
+    }
+}
+
+impl Codec {
+    /// The max amount of bytes needed to detect the codec.
+    pub const MAGIC_LEN: usize = 4;
+
+    /// Detect the codec from the magic bytes at the start of the data.
+    ///
+    /// Returns `None` if more data is needed to tell; the data that is
+    /// not recognized as compressed yields [`Codec::None`].
+    /// Raw deflate and brotli have no magic bytes, and are never detected.
+    /// zlib is not detected either, see [`Self::detect_with_zlib`].
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        Self::detect_impl(data, false)
+    }
+
+    /// Detect the codec from the magic bytes at the start of the data,
+    /// including zlib.
+    ///
+    /// The zlib header is only protected by a five bit checksum, so about
+    /// one in thirty of the plain text starts (i.e. `x^`, `HK` or `(S`)
+    /// are detected as zlib; only use this when the data is known to be
+    /// either zlib or binary.
+    pub fn detect_with_zlib(data: &[u8]) -> Option<Self> {
+        Self::detect_impl(data, true)
+    }
+
+    /// Detect the codec, optionally including zlib.
+    fn detect_impl(data: &[u8], zlib: bool) -> Option<Self> {
+        match data {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            [0x04, 0x22, 0x4d, 0x18, ..] => Some(Self::Lz4),
+            [cmf, flg, ..] if zlib && Self::is_zlib_header(*cmf, *flg) => Some(Self::Zlib),
+            _ if data.len() >= Self::MAGIC_LEN => Some(Self::None),
+            // Might still be one of the magics.
+            []
+            | [0x1f]
+            | [0x28]
+            | [0x28, 0xb5]
+            | [0x28, 0xb5, 0x2f]
+            | [0x04]
+            | [0x04, 0x22]
+            | [0x04, 0x22, 0x4d] => None,
+            [cmf] if zlib && cmf & 0x0f == 8 => None,
+            _ => Some(Self::None),
+        }
+    }
+
+    /// Check whether the bytes form a valid zlib header.
+    fn is_zlib_header(cmf: u8, flg: u8) -> bool {
+        // Deflate with the window size of at most 32K, no preset dictionary,
+        // and the valid header checksum.
+        cmf & 0x0f == 8
+            && cmf >> 4 <= 7
+            && flg & 0x20 == 0
+            && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0
+    }
+}
+
+/// The decompressor for the detected codec.
+#[derive(Debug)]
+enum Detected {
+    /// Not compressed, the data is passed through as is.
+    None,
+    /// gzip.
+    #[cfg(feature = "gzip")]
+    Gzip(Box<Gzip>),
+    /// zlib.
+    #[cfg(feature = "deflate")]
+    Zlib(Zlib),
+    /// zstd.
+    #[cfg(feature = "zstd")]
+    Zstd(Box<Zstd>),
+    /// LZ4 frame format.
+    #[cfg(feature = "lz4")]
+    Lz4(Lz4),
+}
+
+impl Detected {
+    /// Create the decompressor for the given codec.
+    fn new(codec: Codec) -> io::Result<Self> {
+        match codec {
+            Codec::None => Ok(Self::None),
+            #[cfg(feature = "gzip")]
+            Codec::Gzip => Ok(Self::Gzip(Box::default())),
+            #[cfg(feature = "deflate")]
+            Codec::Zlib => Ok(Self::Zlib(Zlib::new())),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Ok(Self::Zstd(Box::new(Zstd::new()?))),
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 => Ok(Self::Lz4(Lz4::new())),
+            #[allow(unreachable_patterns)]
+            codec => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("the data is {codec}-compressed, but {codec} support is not enabled"),
+            )),
+        }
+    }
+
+    /// Decompress the data.
+    fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        match self {
+            Self::None => {
+                output.append(input);
+                Ok(())
+            }
+            #[cfg(feature = "gzip")]
+            Self::Gzip(inner) => inner.transform(input, output),
+            #[cfg(feature = "deflate")]
+            Self::Zlib(inner) => inner.transform(input, output),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(inner) => inner.transform(input, output),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(inner) => inner.transform(input, output),
+        }
+    }
+
+    /// Finish the decompression.
+    fn finish<Output>(&mut self, output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        match self {
+            Self::None => {
+                let _ = output;
+                Ok(())
+            }
+            #[cfg(feature = "gzip")]
+            Self::Gzip(inner) => inner.finish(output),
+            #[cfg(feature = "deflate")]
+            Self::Zlib(inner) => inner.finish(output),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(inner) => inner.finish(output),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(inner) => inner.finish(output),
+        }
+    }
+}
+
+/// The decompressor that detects the codec from the magic bytes at the start
+/// of the stream.
+///
+/// Detects gzip, zstd and LZ4 frames, and, if enabled, zlib; the data that
+/// is not recognized as compressed is passed through as is.
+/// Detecting a codec which support is not enabled is an error.
+#[derive(Debug, Default)]
+pub struct Auto {
+    /// The data received before the codec could be detected.
+    pending: Vec<u8>,
+    /// Whether to detect zlib.
+    zlib: bool,
+    /// The decompressor for the detected codec.
+    detected: Option<(Codec, Detected)>,
+}
+
+impl Auto {
+    /// Create a new [`Auto`] decompressor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether to detect zlib (disabled by default).
+    ///
+    /// See [`Codec::detect_with_zlib`] for the false positives this brings.
+    pub fn with_zlib(mut self, zlib: bool) -> Self {
+        self.zlib = zlib;
+        self
+    }
+
+    /// The detected codec, if it has been detected yet.
+    pub fn codec(&self) -> Option<Codec> {
+        self.detected.as_ref().map(|(codec, _)| *codec)
+    }
+
+    /// Start decompressing with the given codec.
+    fn start<Output>(&mut self, codec: Codec, output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        let mut detected = Detected::new(codec)?;
+        let pending = std::mem::take(&mut self.pending);
+        let result = detected.transform(&pending, output);
+        self.detected = Some((codec, detected));
+        result
+    }
+}
+
+impl Transform for Auto {
+    type Error = io::Error;
+
+    fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        if let Some((_, ref mut detected)) = self.detected {
+            return detected.transform(input, output);
+        }
+        self.pending.extend_from_slice(input);
+        match Codec::detect_impl(&self.pending, self.zlib) {
+            Some(codec) => self.start(codec, output),
+            None => Ok(()),
+        }
+    }
+
+    fn finish<Output>(&mut self, output: &mut Output) -> io::Result<()>
+    where
+        Output: Sink,
+    {
+        if self.detected.is_none() {
+            // Too short to be compressed.
+            self.start(Codec::None, output)?;
+        }
+        match self.detected {
+            Some((_, ref mut detected)) => detected.finish(output),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::transform::Transformed;
+
+    #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn document() -> Vec<u8> {
+        (0..1000)
+            .map(|i| format!(r#"{{"field": "val{i}"}}"#))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    /// Decompress and decode the data fed in small chunks.
+    #[allow(clippy::arithmetic_side_effects)]
+    fn decode<T: Transform<Error = io::Error>>(transform: T, data: &[u8]) -> io::Result<usize> {
+        let mut dec = Transformed::new(
+            transform,
+            crate::State::new(crate::decoder::serde_json::Decoder::<TO>::new(), Vec::new()),
+        );
+
+        let mut count = 0;
+        for chunk in data.chunks(7) {
+            for value in dec.process_next_chunk(chunk).map_err(into_io)? {
+                assert_eq!(value.unwrap().field, format!("val{}", count % 1000));
+                count += 1;
+            }
+        }
+        for value in dec.process_eof().map_err(into_io)? {
+            assert_eq!(value.unwrap().field, format!("val{}", count % 1000));
+            count += 1;
+        }
+        assert!(dec.finish().is_ok());
+        Ok(count)
+    }
+
+    fn into_io(err: crate::transform::Error<io::Error>) -> io::Error {
+        match err {
+            crate::transform::Error::Transform(err) => err,
+            crate::transform::Error::BufferLimitExceeded(err) => io::Error::other(err),
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn assert_truncated<T: Transform<Error = io::Error>>(transform: T, data: &[u8]) {
+        let err = decode(transform, &data[..data.len() - 3]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "gzip")]
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use std::io::Write as _;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[cfg(feature = "deflate")]
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        use std::io::Write as _;
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[cfg(feature = "brotli")]
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        use std::io::Write as _;
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(data).unwrap();
+        encoder.into_inner()
+    }
+
+    #[cfg(feature = "zstd")]
+    fn zstd(data: &[u8]) -> Vec<u8> {
+        zstd::encode_all(data, 0).unwrap()
+    }
+
+    #[cfg(feature = "lz4")]
+    fn lz4(data: &[u8], block_mode: lz4_flex::frame::BlockMode) -> Vec<u8> {
+        use std::io::Write as _;
+        let info = lz4_flex::frame::FrameInfo::new()
+            .block_mode(block_mode)
+            .block_size(lz4_flex::frame::BlockSize::Max64KB)
+            .block_checksums(true)
+            .content_checksum(true);
+        let mut encoder = lz4_flex::frame::FrameEncoder::with_frame_info(info, Vec::new());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// The sink keeping track of the largest piece of output.
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    #[derive(Debug, Default)]
+    struct Largest {
+        largest: usize,
+        total: usize,
+    }
+
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    impl Sink for Largest {
+        #[allow(clippy::arithmetic_side_effects)]
+        fn append(&mut self, data: &[u8]) {
+            self.largest = self.largest.max(data.len());
+            self.total += data.len();
+        }
+    }
+
+    /// Check that the output is produced in bounded pieces.
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    fn assert_bounded<T: Transform<Error = io::Error>>(mut transform: T, compressed: &[u8]) {
+        let mut output = Largest::default();
+        transform.transform(compressed, &mut output).unwrap();
+        transform.finish(&mut output).unwrap();
+        assert_eq!(output.total, 16 * 1024 * 1024);
+        assert!(output.largest <= SCRATCH_SIZE, "{output:?}");
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn test_brotli_bomb() {
+        let compressed = brotli(&vec![b' '; 16 * 1024 * 1024]);
+        assert!(compressed.len() < 1024);
+        assert_bounded(Brotli::new(), &compressed);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_decompression_bomb() {
+        let compressed = gzip(&vec![b' '; 16 * 1024 * 1024]);
+        assert!(compressed.len() < 1024 * 1024);
+        assert_bounded(Gzip::new(), &compressed);
+
+        let mut dec = Transformed::new(
+            Gzip::new(),
+            crate::State::new(crate::decoder::serde_json::Decoder::<TO>::new(), Vec::new())
+                .with_buffer_limit(crate::BufferLimit {
+                    max_bytes: 1024 * 1024,
+                    on_exceeded: crate::OnBufferLimitExceeded::Fail,
+                }),
+        );
+        let err = dec.process_next_chunk(&compressed).err().unwrap();
+        assert!(
+            matches!(err, crate::transform::Error::BufferLimitExceeded(_)),
+            "{err:?}"
+        );
+        assert!(dec.state.buffer.len() <= 1024 * 1024);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    #[allow(clippy::arithmetic_side_effects)]
+    fn test_gzip() {
+        use std::io::Write as _;
+
+        let data = document();
+        let (first, second) = data.split_at(data.len() / 3);
+        // Multiple members, the second one with the optional header fields.
+        let mut compressed = gzip(first);
+        let mut encoder = flate2::GzBuilder::new()
+            .extra(vec![42; 10])
+            .filename("data.json")
+            .comment("the rest of the data")
+            .write(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(second).unwrap();
+        compressed.extend(encoder.finish().unwrap());
+
+        assert_eq!(decode(Gzip::new(), &compressed).unwrap(), 1000);
+        assert_truncated(Gzip::new(), &compressed);
+
+        let mut corrupted = compressed.clone();
+        let crc = corrupted.len() - 8;
+        corrupted[crc] ^= 1;
+        let err = decode(Gzip::new(), &corrupted).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut trailing = compressed;
+        trailing.extend_from_slice(b"garbage");
+        let err = decode(Gzip::new(), &trailing).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn test_deflate() {
+        use std::io::Write as _;
+
+        let data = document();
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(decode(Deflate::new(), &compressed).unwrap(), 1000);
+        assert_truncated(Deflate::new(), &compressed);
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn test_deflate_chunked() {
+        use std::io::Write as _;
+
+        // Highly compressible, so that a single chunk expands past the
+        // scratch buffer.
+        let data = document().repeat(200);
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        for size in [7, 1000, 64 * 1024, compressed.len()] {
+            let mut deflate = Deflate::new();
+            let mut output = Vec::new();
+            for chunk in compressed.chunks(size) {
+                deflate.transform(chunk, &mut output).unwrap();
+            }
+            deflate.finish(&mut output).unwrap();
+            assert!(output == data, "chunk size {size}");
+        }
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn test_zlib() {
+        let compressed = zlib(&document());
+
+        assert_eq!(decode(Zlib::new(), &compressed).unwrap(), 1000);
+        assert_truncated(Zlib::new(), &compressed);
+
+        let mut trailing = compressed.clone();
+        trailing.extend_from_slice(b"garbage");
+        let err = decode(Zlib::new(), &trailing).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        let data = document();
+        let (first, second) = data.split_at(data.len() / 3);
+        // Multiple frames.
+        let mut compressed = zstd(first);
+        compressed.extend(zstd(second));
+
+        assert_eq!(decode(Zstd::new().unwrap(), &compressed).unwrap(), 1000);
+        assert_truncated(Zstd::new().unwrap(), &compressed);
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn test_brotli() {
+        let compressed = brotli(&document());
+
+        assert_eq!(decode(Brotli::new(), &compressed).unwrap(), 1000);
+        assert_truncated(Brotli::new(), &compressed);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4() {
+        // Large enough to span multiple blocks.
+        let data = document().repeat(10);
+
+        for block_mode in [
+            lz4_flex::frame::BlockMode::Independent,
+            lz4_flex::frame::BlockMode::Linked,
+        ] {
+            let mut compressed = lz4(&data, block_mode);
+            // A skippable frame.
+            compressed.extend_from_slice(&0x184D_2A5Au32.to_le_bytes());
+            compressed.extend_from_slice(&3u32.to_le_bytes());
+            compressed.extend_from_slice(b"xyz");
+            compressed.extend(lz4(&document(), block_mode));
+
+            assert_eq!(decode(Lz4::new(), &compressed).unwrap(), 11000);
+            assert_truncated(Lz4::new(), &compressed);
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Codec::detect(b""), None);
+        assert_eq!(Codec::detect(b"\x1f"), None);
+        assert_eq!(Codec::detect(b"\x1f\x8b"), Some(Codec::Gzip));
+        assert_eq!(Codec::detect(b"\x28\xb5\x2f"), None);
+        assert_eq!(Codec::detect(b"\x28\xb5\x2f\xfd"), Some(Codec::Zstd));
+        assert_eq!(Codec::detect(b"\x04\x22\x4d\x18"), Some(Codec::Lz4));
+        assert_eq!(Codec::detect(b"\x78\x9c"), Some(Codec::None));
+        assert_eq!(Codec::detect_with_zlib(b"\x78"), None);
+        assert_eq!(Codec::detect_with_zlib(b"\x78\x9c"), Some(Codec::Zlib));
+        assert_eq!(Codec::detect_with_zlib(b"\x78\x9d"), Some(Codec::None));
+        for text in ["x^abc", "HKLM", "hCount", "(Sorry"] {
+            assert_eq!(Codec::detect(text.as_bytes()), Some(Codec::None), "{text}");
+        }
+        assert_eq!(Codec::detect(b"{"), Some(Codec::None));
+        assert_eq!(Codec::detect(b"xyzw"), Some(Codec::None));
+    }
+
+    #[test]
+    fn test_auto() {
+        let data = document();
+
+        #[allow(unused_mut)]
+        let mut inputs = vec![(Codec::None, data.clone())];
+        #[cfg(feature = "gzip")]
+        inputs.push((Codec::Gzip, gzip(&data)));
+        #[cfg(feature = "deflate")]
+        inputs.push((Codec::Zlib, zlib(&data)));
+        #[cfg(feature = "zstd")]
+        inputs.push((Codec::Zstd, zstd(&data)));
+        #[cfg(feature = "lz4")]
+        inputs.push((Codec::Lz4, lz4(&data, lz4_flex::frame::BlockMode::Linked)));
+
+        for (codec, compressed) in inputs {
+            let make_auto = || Auto::new().with_zlib(codec == Codec::Zlib);
+            let mut auto = make_auto();
+            let mut output = Vec::new();
+            auto.transform(&compressed[..1], &mut output).unwrap();
+            if codec != Codec::None {
+                assert_eq!(auto.codec(), None);
+            }
+
+            assert_eq!(decode(make_auto(), &compressed).unwrap(), 1000);
+
+            auto.transform(&compressed[1..], &mut output).unwrap();
+            auto.finish(&mut output).unwrap();
+            assert_eq!(auto.codec(), Some(codec));
+            assert_eq!(output, data);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! [`encoding_rs`] integration (i.e. transcoding the text to UTF-8).
+
+use super::Sink;
+
+/// The max amount of input bytes to transcode at once.
+const MAX_INPUT_CHUNK: usize = 64 * 1024;
+
+/// The transform that transcodes the text from the given encoding to UTF-8,
+/// so that it can be consumed by the decoders that only support UTF-8 (i.e.
+/// [`crate::decoder::serde_json`]).
+///
+/// The code units split across the chunks are handled.
+/// The BOM, if present, overrides the given encoding and is removed from
+/// the output (see [`encoding_rs::Encoding::new_decoder`]).
+///
+/// By default, the malformed input is replaced with the REPLACEMENT
+/// CHARACTER (U+FFFD); use [`Transform::with_strict`] to report it as
+/// an error instead.
+#[derive(Debug)]
+pub struct Transform {
+    /// The underlying decoder.
+    inner: encoding_rs::Decoder,
+    /// Whether to fail on the malformed input instead of replacing it.
+    strict: bool,
+    /// The intermediate buffer for the output.
+    scratch: Vec<u8>,
+}
+
+impl Transform {
+    /// Create a new [`Transform`] for the given encoding, with BOM sniffing.
+    pub fn new(encoding: &'static encoding_rs::Encoding) -> Self {
+        Self::with_decoder(encoding.new_decoder())
+    }
+
+    /// Create a new [`Transform`] with the given [`encoding_rs::Decoder`],
+    /// i.e. to change the BOM handling.
+    pub fn with_decoder(decoder: encoding_rs::Decoder) -> Self {
+        Self {
+            inner: decoder,
+            strict: false,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Report the malformed input as an error instead of replacing it.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// The encoding being transcoded from.
+    ///
+    /// Reflects the BOM once it has been detected.
+    pub fn encoding(&self) -> &'static encoding_rs::Encoding {
+        self.inner.encoding()
+    }
+
+    /// Transcode the data, appending the output to the buffer.
+    fn decode<Output>(&mut self, input: &[u8], output: &mut Output, last: bool) -> Result<(), Error>
+    where
+        Output: Sink,
+    {
+        let mut chunks = input.chunks(MAX_INPUT_CHUNK).peekable();
+        if chunks.peek().is_none() {
+            // Still let the decoder flush at the end of input.
+            return self.decode_chunk(&[], output, last);
+        }
+        // Transcode the rest of the input even after the malformed input, so
+        // that the error does not drop the data.
+        let mut result = Ok(());
+        while let Some(chunk) = chunks.next() {
+            let chunk_result = self.decode_chunk(chunk, output, last && chunks.peek().is_none());
+            result = result.and(chunk_result);
+        }
+        result
+    }
+
+    /// Transcode the chunk of data, appending the output to the buffer.
+    fn decode_chunk<Output>(
+        &mut self,
+        chunk: &[u8],
+        output: &mut Output,
+        last: bool,
+    ) -> Result<(), Error>
+    where
+        Output: Sink,
+    {
+        let mut input = chunk;
+        let mut malformed = false;
+        loop {
+            let max_len = if self.strict {
+                self.inner
+                    .max_utf8_buffer_length_without_replacement(input.len())
+            } else {
+                self.inner.max_utf8_buffer_length(input.len())
+            };
+            self.scratch.resize(
+                max_len.expect("the chunk size is bounded, so the output length does not overflow"),
+                0,
+            );
+
+            let (done, read, written) = if self.strict {
+                let (result, read, written) =
+                    self.inner
+                        .decode_to_utf8_without_replacement(input, &mut self.scratch, last);
+                match result {
+                    encoding_rs::DecoderResult::InputEmpty => (true, read, written),
+                    encoding_rs::DecoderResult::OutputFull => (false, read, written),
+                    encoding_rs::DecoderResult::Malformed(..) => {
+                        // Carry on past the malformed sequence, which is
+                        // consumed by the decoder.
+                        malformed = true;
+                        (false, read, written)
+                    }
+                }
+            } else {
+                let (result, read, written, _replaced) =
+                    self.inner.decode_to_utf8(input, &mut self.scratch, last);
+                (
+                    result == encoding_rs::CoderResult::InputEmpty,
+                    read,
+                    written,
+                )
+            };
+
+            output.append(self.scratch.get(..written).unwrap_or_default());
+            input = input.get(read..).unwrap_or_default();
+            if done {
+                break;
+            }
+        }
+
+        if malformed {
+            return Err(Error {
+                encoding: self.encoding(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl super::Transform for Transform {
+    type Error = Error;
+
+    fn transform<Output>(&mut self, input: &[u8], output: &mut Output) -> Result<(), Error>
+    where
+        Output: Sink,
+    {
+        self.decode(input, output, false)
+    }
+
+    fn finish<Output>(&mut self, output: &mut Output) -> Result<(), Error>
+    where
+        Output: Sink,
+    {
+        self.decode(&[], output, true)
+    }
+}
+
+/// The error indicating the malformed input in the strict mode.
+///
+/// The malformed input is dropped, but the rest of the chunk is still
+/// transcoded, and the transcoding can be continued with the next chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// The encoding of the input.
+    pub encoding: &'static encoding_rs::Encoding,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "malformed {} input", self.encoding.name())
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::transform::Transformed;
+
+    #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+    struct TestObject {
+        pub field: String,
+    }
+
+    type TO = TestObject;
+
+    fn make_state(
+        transform: Transform,
+    ) -> Transformed<Transform, crate::decoder::serde_json::Decoder<TO>, Vec<u8>> {
+        Transformed::new(
+            transform,
+            crate::State::new(crate::decoder::serde_json::Decoder::new(), Vec::new()),
+        )
+    }
+
+    fn decode(transform: Transform, data: &[u8], chunk_size: usize) -> Vec<String> {
+        let mut dec = make_state(transform);
+
+        let mut values = Vec::new();
+        for chunk in data.chunks(chunk_size) {
+            for value in dec.process_next_chunk(chunk).unwrap() {
+                values.push(value.unwrap().field);
+            }
+        }
+        for value in dec.process_eof().unwrap() {
+            values.push(value.unwrap().field);
+        }
+        assert!(dec.finish().is_ok());
+        values
+    }
+
+    fn utf16(text: &str, bom: &[u8], to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        let mut data = bom.to_vec();
+        data.extend(text.encode_utf16().flat_map(to_bytes));
+        data
+    }
+
+    const TEXT: &str = r#"{"field": "val0 ü"}{"field": "val1 😀"}"#;
+
+    #[test]
+    fn test_utf16le_bom() {
+        let data = utf16(TEXT, b"\xff\xfe", u16::to_le_bytes);
+
+        // Odd chunk sizes split the code units, and the BOM.
+        for chunk_size in [1, 3, 7, data.len()] {
+            // The BOM overrides the encoding.
+            let transform = Transform::new(encoding_rs::UTF_8);
+            assert_eq!(decode(transform, &data, chunk_size), ["val0 ü", "val1 😀"]);
+        }
+    }
+
+    #[test]
+    fn test_utf16be_bom() {
+        let data = utf16(TEXT, b"\xfe\xff", u16::to_be_bytes);
+
+        let mut dec = make_state(Transform::new(encoding_rs::UTF_8));
+        {
+            let mut stream = dec.process_next_chunk(&data[..5]).unwrap();
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(dec.transform.encoding(), encoding_rs::UTF_16BE);
+    }
+
+    #[test]
+    fn test_windows_1252() {
+        let (data, _, _) = encoding_rs::WINDOWS_1252.encode(r#"{"field": "café"}"#);
+
+        let transform = Transform::new(encoding_rs::WINDOWS_1252);
+        assert_eq!(decode(transform, &data, 2), ["café"]);
+
+        // Latin-1 is treated as Windows-1252.
+        let encoding = encoding_rs::Encoding::for_label(b"latin1").unwrap();
+        assert_eq!(decode(Transform::new(encoding), &data, 2), ["café"]);
+    }
+
+    #[test]
+    fn test_malformed() {
+        // The unpaired surrogate.
+        let mut data = utf16(r#"{"field": "a"}"#, b"", u16::to_le_bytes);
+        data.extend_from_slice(&0xd800u16.to_le_bytes());
+
+        // Replaced by default.
+        let mut output = Vec::new();
+        let mut transform = Transform::new(encoding_rs::UTF_16LE);
+        super::super::Transform::transform(&mut transform, &data, &mut output).unwrap();
+        super::super::Transform::finish(&mut transform, &mut output).unwrap();
+        assert_eq!(output, r#"{"field": "a"}�"#.as_bytes());
+
+        // Reported in the strict mode.
+        let mut dec = make_state(Transform::new(encoding_rs::UTF_16LE).with_strict(true));
+        {
+            let mut stream = dec.process_next_chunk(&data).unwrap();
+            assert_eq!(stream.next().unwrap().unwrap().field, "a");
+            assert!(stream.next().is_none());
+        }
+        assert_eq!(
+            dec.process_eof().err(),
+            Some(crate::transform::Error::Transform(Error {
+                encoding: encoding_rs::UTF_16LE
+            }))
+        );
+    }
+
+    #[test]
+    fn test_malformed_in_the_middle() {
+        let mut output = Vec::new();
+        let mut transform = Transform::new(encoding_rs::UTF_8).with_strict(true);
+
+        assert_eq!(
+            super::super::Transform::transform(&mut transform, b"ab\xffcd", &mut output),
+            Err(Error {
+                encoding: encoding_rs::UTF_8
+            })
+        );
+        // The data after the malformed input is kept.
+        assert_eq!(output, b"abcd");
+
+        super::super::Transform::transform(&mut transform, b"ef", &mut output).unwrap();
+        super::super::Transform::finish(&mut transform, &mut output).unwrap();
+        assert_eq!(output, b"abcdef");
+    }
+
+    #[test]
+    fn test_malformed_in_a_large_chunk() {
+        let mut output = Vec::new();
+        let mut transform = Transform::new(encoding_rs::UTF_8).with_strict(true);
+
+        // The chunk spans multiple transcoding steps.
+        let mut data = b"ab\xffcd".to_vec();
+        data.extend_from_slice(&[b'x'; 2 * MAX_INPUT_CHUNK]);
+        assert_eq!(
+            super::super::Transform::transform(&mut transform, &data, &mut output),
+            Err(Error {
+                encoding: encoding_rs::UTF_8
+            })
+        );
+        // The data after the malformed input is kept.
+        assert_eq!(output.len(), data.len() - 1);
+        assert!(output.starts_with(b"abcdx"));
+    }
+}